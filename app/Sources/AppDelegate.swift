@@ -9,7 +9,9 @@ final class AppDelegate: UIResponder, UIApplicationDelegate {
         let aptosRestURL = "https://fullnode.devnet.aptoslabs.com"
         let aptosFaucetURL = "https://faucet.devnet.aptoslabs.com"
 
-        return create_core("info", aptosRestURL, aptosFaucetURL)
+        let eventCallback = rustEventCallback { event in print("Core event: \(event)") }
+
+        return create_core("info", aptosRestURL, aptosFaucetURL, eventCallback)
     }
 
     private var firestore: Firestore {
@@ -130,3 +130,40 @@ func rustCallAsyncAwait<Response: SwiftProtobuf.Message>(
         }
     })
 }
+
+// MARK: - Events
+
+private class SwiftEventCallback {
+
+    private let closure: (CoreProto_Event) -> Void
+
+    init(closure: @escaping (CoreProto_Event) -> Void) {
+        self.closure = closure
+    }
+
+    func run(_ data: Data?) {
+        guard let data = data, let event = try? CoreProto_Event(serializedData: data) else { return }
+
+        DispatchQueue.main.async { self.closure(event) }
+    }
+}
+
+// The returned callback is given to `create_core` and is called for every event the core pushes
+func rustEventCallback(closure: @escaping (CoreProto_Event) -> Void) -> RustCallback {
+    let swiftCallback = SwiftEventCallback(closure: closure)
+    // Retained for the lifetime of the core
+    let swiftCallbackPtr = Unmanaged.passRetained(swiftCallback).toOpaque()
+
+    return RustCallback(
+        swift_callback_ptr: swiftCallbackPtr,
+        callback: { (swiftCallbackPtr: UnsafeRawPointer?, event: RustData) in
+            let (data, _) = rustDataToData(event)
+
+            defer { rust_free_data(event) }
+
+            let swiftCallback: SwiftEventCallback = Unmanaged.fromOpaque(swiftCallbackPtr!).takeUnretainedValue()
+
+            swiftCallback.run(data)
+        }
+    )
+}
@@ -8,7 +8,9 @@ public final class OldWalletViewController: UIViewController {
         let aptosRestURL = "https://fullnode.devnet.aptoslabs.com"
         let aptosFaucetURL = "https://faucet.devnet.aptoslabs.com"
 
-        return create_core("info", aptosRestURL, aptosFaucetURL)
+        let eventCallback = rustEventCallback { event in print("Core event: \(event)") }
+
+        return create_core("info", aptosRestURL, aptosFaucetURL, eventCallback)
     }
 
     var db: Firestore!
@@ -6,7 +6,9 @@ final class TestViewController: UIViewController {
         let aptosRestURL = "https://fullnode.devnet.aptoslabs.com"
         let aptosFaucetURL = "https://faucet.devnet.aptoslabs.com"
 
-        return create_core("info", aptosRestURL, aptosFaucetURL)
+        let eventCallback = rustEventCallback { event in print("Core event: \(event)") }
+
+        return create_core("info", aptosRestURL, aptosFaucetURL, eventCallback)
     }
     
     override func viewDidLoad() {
@@ -3,6 +3,8 @@ use rand::{SeedableRng, Rng, rngs::StdRng, rngs::OsRng};
 use hex::ToHex;
 use ed25519_dalek::Signer;
 
+use crate::aptos::*;
+
 pub struct AptosAccount {
     pub keypair: Keypair,
 }
@@ -20,12 +22,41 @@ impl AptosAccount {
 
         return Self { keypair };
     }
+
+    /// Same as `new` with a hex encoded keypair but fails instead of panicking
+    pub fn from_keypair(keypair: &str) -> Result<Self, AptosError> {
+        let keypair_bytes = match hex::decode(keypair) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidKeypair)
+            },
+        };
+
+        return match Keypair::from_bytes(&keypair_bytes) {
+            Ok(keypair) => Ok(Self { keypair }),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::InvalidKeypair)
+            },
+        };
+    }
     
     /// Returns the public key hex encoded
     pub fn public_key(&self) -> String {
         return hex::encode(self.keypair.public.as_bytes());
     }
 
+    /// Returns the authentication key of the account: sha3_256(public_key || 0x00)
+    pub fn authentication_key(&self) -> AccountAddress {
+        return authentication_key(self.keypair.public.as_bytes(), AuthenticationScheme::Ed25519);
+    }
+
+    /// Returns the address of the account, the authentication key until a rotation
+    pub fn address(&self) -> AccountAddress {
+        return self.authentication_key();
+    }
+
     /// Returns the keypair in bytes
     pub fn keypair(&self) -> String {
         return hex::encode(self.keypair.to_bytes());
@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+
+const BITMAP_NUM_OF_BYTES: usize = MAX_PUBLIC_KEYS / 8;
+
+/// The account a transaction is sent from, chooses the authenticator of the submitted transaction
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccountSigner {
+    /// Account created with a single ed25519 key
+    Single {
+        public_key: String,
+    },
+    /// Shared wallet, any `threshold` of the `public_keys` can sign
+    Multi {
+        public_keys: Vec<String>,
+        threshold: u8,
+    },
+}
+
+impl AccountSigner {
+    pub fn single(public_key: &str) -> Self {
+        return Self::Single {
+            public_key: normalize_hex(public_key),
+        };
+    }
+
+    pub fn multi(wallet: &AptosSharedWallet) -> Self {
+        return Self::Multi {
+            public_keys: wallet.public_keys.iter().map(|key| hex::encode(key.as_bytes())).collect(),
+            threshold: wallet.threshold(),
+        };
+    }
+
+    pub fn scheme(&self) -> AuthenticationScheme {
+        return match self {
+            Self::Single { .. } => AuthenticationScheme::Ed25519,
+            Self::Multi { .. } => AuthenticationScheme::MultiEd25519,
+        };
+    }
+
+    /// Returns the public key bytes as stored on chain: the key, or the keys followed by the threshold
+    pub fn public_key_material(&self) -> Result<Vec<u8>, AptosError> {
+        let (public_keys, threshold) = match self {
+            Self::Single { public_key } => (vec![public_key.clone()], None),
+            Self::Multi { public_keys, threshold } => (public_keys.clone(), Some(*threshold)),
+        };
+
+        let mut public_keys_bytes: Vec<Vec<u8>> = Vec::new();
+
+        for public_key in public_keys {
+            match hex::decode(&public_key) {
+                Ok(bytes) => public_keys_bytes.push(bytes),
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidKeypair)
+                },
+            }
+        }
+
+        return Ok(public_key_material(&public_keys_bytes, threshold));
+    }
+
+    /// Returns the authentication key of the account, which is its address until a rotation
+    pub fn authentication_key(&self) -> Result<AccountAddress, AptosError> {
+        return match self.public_key_material() {
+            Ok(public_key_material) => Ok(authentication_key(&public_key_material, self.scheme())),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Returns the signature bytes as checked on chain: the signature, or the signatures ordered as the keys followed by the bitmap
+    pub fn signature_bytes(&self, signed_payloads: &[SignedPayload]) -> Result<Vec<u8>, AptosError> {
+        let (signatures, bitmap) = match self {
+            Self::Single { public_key } => match single_signature(public_key, signed_payloads) {
+                Ok(signature) => (vec![signature], None),
+                Err(error) => return Err(error),
+            },
+            Self::Multi { public_keys, threshold } => match multi_signatures(public_keys, *threshold, signed_payloads) {
+                Ok((signatures, bitmap)) => (signatures, Some(bitmap)),
+                Err(error) => return Err(error),
+            },
+        };
+
+        let mut signature_bytes: Vec<u8> = Vec::new();
+
+        for signature in signatures {
+            match hex::decode(signature) {
+                Ok(bytes) => signature_bytes.extend_from_slice(&bytes),
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidSignature)
+                },
+            }
+        }
+
+        if let Some(bitmap) = bitmap {
+            signature_bytes.extend_from_slice(&bitmap);
+        }
+
+        return Ok(signature_bytes);
+    }
+
+    /// Returns the number of valid signatures required to submit a transaction
+    pub fn threshold(&self) -> usize {
+        return match self {
+            Self::Single { .. } => 1,
+            Self::Multi { threshold, .. } => *threshold as usize,
+        };
+    }
+
+    /// Checks each signature against its public key and the signing message of the transaction
+    pub fn verify(&self, signing_message: &[u8], signed_payloads: &[SignedPayload]) -> Vec<SignatureVerification> {
+        return signed_payloads.iter().map(|signed_payload| {
+            let public_key = normalize_hex(&signed_payload.public_key);
+
+            let is_signer = match self {
+                Self::Single { public_key: key } => *key == public_key,
+                Self::Multi { public_keys, .. } => public_keys.contains(&public_key),
+            };
+
+            let status = match is_signer {
+                true => verify_signature(&public_key, signing_message, &signed_payload.signature),
+                false => SignatureStatus::UnknownSigner,
+            };
+
+            SignatureVerification {
+                public_key: public_key,
+                status: status,
+            }
+        }).collect();
+    }
+
+    /// Same as `authenticator` with only the valid signatures, fails until the threshold of valid signatures is met
+    pub fn verified_authenticator(&self, signing_message: &[u8], signed_payloads: &[SignedPayload]) -> Result<serde_json::Value, AptosError> {
+        let verifications = self.verify(signing_message, signed_payloads);
+
+        let mut valid_payloads: Vec<SignedPayload> = Vec::new();
+
+        for (signed_payload, verification) in signed_payloads.iter().zip(verifications.iter()) {
+            match verification.status {
+                SignatureStatus::Valid => valid_payloads.push(signed_payload.clone()),
+                status => log::error!("Signature of {} rejected: {:?}", verification.public_key, status),
+            }
+        }
+
+        if valid_payloads.len() < self.threshold() {
+            log::error!("{} valid signatures, {} required", valid_payloads.len(), self.threshold());
+            return Err(AptosError::NotEnoughSignatures);
+        }
+
+        return self.authenticator(&valid_payloads);
+    }
+
+    /// Returns the signature payload to submit with the transaction for the collected signatures
+    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/schemas/TransactionSignature
+    pub fn authenticator(&self, signed_payloads: &[SignedPayload]) -> Result<serde_json::Value, AptosError> {
+        return match self {
+            Self::Single { public_key } => single_authenticator(public_key, signed_payloads),
+            Self::Multi { public_keys, threshold } => multi_authenticator(public_keys, *threshold, signed_payloads),
+        };
+    }
+}
+
+/// Returns the signature of the key, hex encoded without prefix
+fn single_signature(public_key: &str, signed_payloads: &[SignedPayload]) -> Result<String, AptosError> {
+    return match signed_payloads.iter().find(|s| normalize_hex(&s.public_key) == public_key) {
+        Some(signed_payload) => Ok(normalize_hex(&signed_payload.signature)),
+        None => {
+            log::error!("No signature for the public key {}", public_key);
+            Err(AptosError::NotEnoughSignatures)
+        },
+    };
+}
+
+/// Returns the signatures ordered as the public keys of the wallet, hex encoded without prefix, and their bitmap
+fn multi_signatures(
+    public_keys: &[String],
+    threshold: u8,
+    signed_payloads: &[SignedPayload],
+) -> Result<(Vec<String>, [u8; BITMAP_NUM_OF_BYTES]), AptosError> {
+    let mut indexed_signatures: Vec<(usize, String)> = Vec::new();
+
+    for signed_payload in signed_payloads {
+        let signer_public_key = normalize_hex(&signed_payload.public_key);
+
+        let index = match public_keys.iter().position(|key| *key == signer_public_key) {
+            Some(index) => index,
+            None => {
+                log::error!("{} is not a public key of the wallet", signer_public_key);
+                return Err(AptosError::UnknownSigner)
+            },
+        };
+
+        if indexed_signatures.iter().all(|(i, _)| *i != index) {
+            indexed_signatures.push((index, normalize_hex(&signed_payload.signature)));
+        }
+    }
+
+    if indexed_signatures.len() < threshold as usize {
+        log::error!("{} signatures collected, {} required", indexed_signatures.len(), threshold);
+        return Err(AptosError::NotEnoughSignatures);
+    }
+
+    indexed_signatures.sort_by_key(|(index, _)| *index);
+
+    // Accounts registered with more keys than the bitmap holds can't be signed for
+    if public_keys.len() > MAX_PUBLIC_KEYS {
+        log::error!("{} public keys, at most {} can sign", public_keys.len(), MAX_PUBLIC_KEYS);
+        return Err(AptosError::TooManyPublicKeys);
+    }
+
+    let mut bitmap = [0u8; BITMAP_NUM_OF_BYTES];
+
+    for (index, _) in &indexed_signatures {
+        bitmap_set_bit(&mut bitmap, *index);
+    }
+
+    let signatures: Vec<String> = indexed_signatures.into_iter().map(|(_, signature)| signature).collect();
+
+    return Ok((signatures, bitmap));
+}
+
+fn single_authenticator(public_key: &str, signed_payloads: &[SignedPayload]) -> Result<serde_json::Value, AptosError> {
+    let signature = match single_signature(public_key, signed_payloads) {
+        Ok(signature) => signature,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(serde_json::json!({
+        "type": "ed25519_signature",
+        "public_key": format!("0x{}", public_key),
+        "signature": format!("0x{}", signature),
+    }));
+}
+
+fn multi_authenticator(
+    public_keys: &[String],
+    threshold: u8,
+    signed_payloads: &[SignedPayload],
+) -> Result<serde_json::Value, AptosError> {
+    let (signatures, bitmap) = match multi_signatures(public_keys, threshold, signed_payloads) {
+        Ok(result) => result,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(serde_json::json!({
+        "type": "multi_ed25519_signature",
+        "public_keys": public_keys.iter().map(|key| format!("0x{}", key)).collect::<Vec<String>>(),
+        "signatures": signatures.iter().map(|signature| format!("0x{}", signature)).collect::<Vec<String>>(),
+        "threshold": threshold,
+        "bitmap": format!("0x{}", hex::encode(bitmap)),
+    }));
+}
+
+fn bitmap_set_bit(input: &mut [u8; BITMAP_NUM_OF_BYTES], index: usize) {
+    let bucket = index / 8;
+    // Indexes are below MAX_PUBLIC_KEYS, checked by the callers
+    let bucket_pos = index - (bucket * 8);
+    input[bucket] |= 128 >> bucket_pos as u8;
+}
+
+/// Lowercase hex without the `0x` prefix
+fn normalize_hex(value: &str) -> String {
+    return value.trim_start_matches("0x").to_lowercase();
+}
@@ -0,0 +1,151 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::aptos::*;
+
+const ADDRESS_LENGTH: usize = 32;
+
+/// 32 bytes account address.
+/// Parsed from the short (`0x1`) or long (64 hex characters) form, with or without the `0x` prefix,
+/// and always displayed in the long form with the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AccountAddress([u8; ADDRESS_LENGTH]);
+
+impl AccountAddress {
+    pub fn new(bytes: [u8; ADDRESS_LENGTH]) -> Self {
+        return Self(bytes);
+    }
+
+    pub fn parse(address: &str) -> Result<Self, AptosError> {
+        return match Self::validate(address) {
+            Ok(address) => Ok(address),
+            Err(reason) => {
+                log::error!("Invalid address {}: {}", address, reason);
+                Err(AptosError::InvalidAddress)
+            },
+        };
+    }
+
+    /// Same as `parse` but returns the reason why the address is invalid
+    pub fn validate(address: &str) -> Result<Self, &'static str> {
+        let address = address.trim();
+
+        // A single prefix, `0x0x1` is not an address
+        let hex_address = match address.get(..2) {
+            Some("0x") | Some("0X") => &address[2..],
+            _ => address,
+        };
+
+        if hex_address.is_empty() {
+            return Err("empty address");
+        }
+
+        if hex_address.len() > ADDRESS_LENGTH * 2 {
+            return Err("address longer than 32 bytes");
+        }
+
+        if !hex_address.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("address is not hex encoded");
+        }
+
+        // Short addresses are left padded with zeros
+        let bytes = hex::decode(format!("{:0>64}", hex_address)).unwrap();
+
+        let mut output = [0u8; ADDRESS_LENGTH];
+        output.copy_from_slice(&bytes);
+
+        return Ok(Self(output));
+    }
+
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_LENGTH] {
+        return &self.0;
+    }
+
+    /// Returns the long form without the `0x` prefix
+    pub fn to_hex(&self) -> String {
+        return hex::encode(self.0);
+    }
+
+    /// Returns the address without its leading zeros, `0x1` for the framework address
+    pub fn to_short_string(&self) -> String {
+        let hex_address = self.to_hex();
+        let trimmed = hex_address.trim_start_matches('0');
+
+        return match trimmed.is_empty() {
+            true => "0x0".to_string(),
+            false => format!("0x{}", trimmed),
+        };
+    }
+}
+
+impl fmt::Display for AccountAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "0x{}", self.to_hex());
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = AptosError;
+
+    fn from_str(address: &str) -> Result<Self, AptosError> {
+        return Self::parse(address);
+    }
+}
+
+/// Stored in the long form with the prefix
+impl Serialize for AccountAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.to_string());
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = match String::deserialize(deserializer) {
+            Ok(address) => address,
+            Err(error) => return Err(error),
+        };
+
+        return Self::validate(&address).map_err(serde::de::Error::custom);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_short_and_long_forms() {
+        let framework = AccountAddress::parse("0x1").unwrap();
+
+        assert_eq!(framework.to_string(), format!("0x{:0>64}", "1"));
+        assert_eq!(framework.to_short_string(), "0x1");
+        assert_eq!(AccountAddress::parse("1").unwrap(), framework);
+        assert_eq!(AccountAddress::parse(&format!("{:0>64}", "1")).unwrap(), framework);
+        assert_eq!(AccountAddress::parse(" 0x1 ").unwrap(), framework);
+    }
+
+    #[test]
+    fn prefix_is_case_insensitive() {
+        assert_eq!(AccountAddress::parse("0XaB").unwrap(), AccountAddress::parse("0xab").unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        assert_eq!(AccountAddress::validate("0x0x1"), Err("address is not hex encoded"));
+        assert_eq!(AccountAddress::validate("0x"), Err("empty address"));
+        assert_eq!(AccountAddress::validate(""), Err("empty address"));
+        assert_eq!(AccountAddress::validate("0xg1"), Err("address is not hex encoded"));
+        assert_eq!(AccountAddress::validate(&format!("0x1{:0>64}", "")), Err("address longer than 32 bytes"));
+        assert!(matches!(AccountAddress::parse("0x0x1"), Err(AptosError::InvalidAddress)));
+    }
+
+    #[test]
+    fn deserializes_validated_addresses() {
+        let address: AccountAddress = serde_json::from_str("\"0x1\"").unwrap();
+
+        assert_eq!(serde_json::to_string(&address).unwrap(), format!("\"0x{:0>64}\"", "1"));
+        assert!(serde_json::from_str::<AccountAddress>("\"0x0x1\"").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use tiny_keccak::{Sha3, Hasher};
+
+use crate::aptos::*;
+
+/// Byte appended to the public key material before hashing, tells the kind of account
+/// Specs here https://aptos.dev/concepts/accounts#authentication-key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthenticationScheme {
+    Ed25519 = 0,
+    MultiEd25519 = 1,
+}
+
+/// Returns the public key bytes as stored on chain: the key, or the keys followed by the threshold
+pub fn public_key_material<K: AsRef<[u8]>>(public_keys: &[K], threshold: Option<u8>) -> Vec<u8> {
+    let mut public_key_material: Vec<u8> = Vec::new();
+
+    for public_key in public_keys {
+        public_key_material.extend_from_slice(public_key.as_ref());
+    }
+
+    if let Some(threshold) = threshold {
+        public_key_material.push(threshold);
+    }
+
+    return public_key_material;
+}
+
+/// Returns sha3_256(public_key_material || scheme).
+/// The address of an account is its authentication key at creation.
+pub fn authentication_key(public_key_material: &[u8], scheme: AuthenticationScheme) -> AccountAddress {
+    let mut sha3 = Sha3::v256();
+
+    sha3.update(public_key_material);
+    sha3.update(&[scheme as u8]);
+
+    let mut output = [0u8; 32];
+    sha3.finalize(&mut output);
+
+    return AccountAddress::new(output);
+}
+
+pub fn sha3_256(data: &[u8]) -> [u8; 32] {
+    let mut sha3 = Sha3::v256();
+    sha3.update(data);
+
+    let mut output = [0u8; 32];
+    sha3.finalize(&mut output);
+
+    return output;
+}
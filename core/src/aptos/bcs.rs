@@ -0,0 +1,115 @@
+/// Minimal BCS (Binary Canonical Serialization) writer, the encoding used by Aptos to sign and hash transactions
+/// Specs here https://github.com/diem/bcs
+pub struct BcsSerializer {
+    output: Vec<u8>,
+}
+
+impl BcsSerializer {
+    pub fn new() -> Self {
+        return Self {
+            output: Vec::new(),
+        };
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.output.push(value);
+        return self;
+    }
+
+    pub fn u16(&mut self, value: u16) -> &mut Self {
+        self.output.extend_from_slice(&value.to_le_bytes());
+        return self;
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.output.extend_from_slice(&value.to_le_bytes());
+        return self;
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.output.extend_from_slice(&value.to_le_bytes());
+        return self;
+    }
+
+    pub fn u128(&mut self, value: u128) -> &mut Self {
+        self.output.extend_from_slice(&value.to_le_bytes());
+        return self;
+    }
+
+    pub fn bool(&mut self, value: bool) -> &mut Self {
+        self.output.push(value as u8);
+        return self;
+    }
+
+    /// Variable length integer used for lengths and enum variants
+    pub fn uleb128(&mut self, mut value: u64) -> &mut Self {
+        while value >= 0x80 {
+            self.output.push((value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+
+        self.output.push(value as u8);
+        return self;
+    }
+
+    /// Bytes prefixed by their length
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.uleb128(value.len() as u64);
+        self.output.extend_from_slice(value);
+        return self;
+    }
+
+    pub fn str(&mut self, value: &str) -> &mut Self {
+        return self.bytes(value.as_bytes());
+    }
+
+    /// Bytes without length prefix, for fixed size values like addresses
+    pub fn fixed_bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.output.extend_from_slice(value);
+        return self;
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        return self.output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<F: FnOnce(&mut BcsSerializer)>(f: F) -> String {
+        let mut serializer = BcsSerializer::new();
+        f(&mut serializer);
+
+        return hex::encode(serializer.finish());
+    }
+
+    #[test]
+    fn integers_are_little_endian() {
+        assert_eq!(encode(|s| { s.u8(0x01); }), "01");
+        assert_eq!(encode(|s| { s.u16(0x0102); }), "0201");
+        assert_eq!(encode(|s| { s.u32(0x01020304); }), "04030201");
+        assert_eq!(encode(|s| { s.u64(0x0102030405060708); }), "0807060504030201");
+        assert_eq!(encode(|s| { s.u128(1); }), "01000000000000000000000000000000");
+        assert_eq!(encode(|s| { s.bool(true).bool(false); }), "0100");
+    }
+
+    #[test]
+    fn uleb128_uses_seven_bits_per_byte() {
+        assert_eq!(encode(|s| { s.uleb128(0); }), "00");
+        assert_eq!(encode(|s| { s.uleb128(127); }), "7f");
+        assert_eq!(encode(|s| { s.uleb128(128); }), "8001");
+        assert_eq!(encode(|s| { s.uleb128(300); }), "ac02");
+        assert_eq!(encode(|s| { s.uleb128(16384); }), "808001");
+        assert_eq!(encode(|s| { s.uleb128(u32::MAX as u64); }), "ffffffff0f");
+    }
+
+    #[test]
+    fn bytes_are_prefixed_by_their_length() {
+        assert_eq!(encode(|s| { s.bytes(&[]); }), "00");
+        assert_eq!(encode(|s| { s.str("coin"); }), "04636f696e");
+        assert_eq!(encode(|s| { s.bytes(&[0xab; 128]); }), format!("8001{}", "ab".repeat(128)));
+        assert_eq!(encode(|s| { s.fixed_bytes(&[0xab, 0xcd]); }), "abcd");
+    }
+}
@@ -0,0 +1,263 @@
+use core::time::Duration;
+use std::sync::RwLock;
+
+use crate::aptos::*;
+
+/// Source of the current time, replaced in tests to simulate a wrong device clock
+pub trait Clock: Send + Sync {
+    /// Time elapsed since the Unix epoch
+    fn now(&self) -> Result<Duration, AptosError>;
+}
+
+/// Clock of the device
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<Duration, AptosError> {
+        return match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            Ok(time) => Ok(time),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::TimeWentBackwards)
+            },
+        };
+    }
+}
+
+/// Local clock corrected by its offset to the chain, measured with the ledger timestamp of the node.
+/// Transaction expirations are checked against the chain time, a device clock ahead or behind
+/// would otherwise produce transactions already expired or valid for too long.
+pub struct LedgerClock {
+    clock: Box<dyn Clock>,
+    /// Ledger time minus local time in microseconds, `None` until the ledger timestamp is known
+    offset_micros: RwLock<Option<i64>>,
+    /// Local time of the last measure, `None` once invalidated
+    observed_at: RwLock<Option<Duration>>,
+}
+
+impl LedgerClock {
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        return Self {
+            clock: clock,
+            offset_micros: RwLock::new(None),
+            observed_at: RwLock::new(None),
+        };
+    }
+
+    /// Measures the offset with the ledger timestamp of the node in microseconds, returns the offset
+    pub fn observe(&self, ledger_timestamp: &str) -> Result<i64, AptosError> {
+        let ledger_micros = match ledger_timestamp.parse::<i64>() {
+            Ok(micros) => micros,
+            Err(error) => {
+                log::error!("Invalid ledger timestamp {}: {}", ledger_timestamp, error);
+                return Err(AptosError::InvalidResponse)
+            },
+        };
+
+        let local_time = match self.clock.now() {
+            Ok(time) => time,
+            Err(error) => return Err(error),
+        };
+
+        let offset_micros = ledger_micros - local_time.as_micros() as i64;
+
+        *self.offset_micros.write().unwrap() = Some(offset_micros);
+        *self.observed_at.write().unwrap() = Some(local_time);
+
+        return Ok(offset_micros);
+    }
+
+    pub fn offset_micros(&self) -> Option<i64> {
+        return *self.offset_micros.read().unwrap();
+    }
+
+    /// True when the offset is unknown, invalidated or measured more than `max_age` ago,
+    /// the device clock drifts or may be changed by the user
+    pub fn needs_sync(&self, max_age: Duration) -> bool {
+        let observed_at = match *self.observed_at.read().unwrap() {
+            Some(observed_at) => observed_at,
+            None => return true,
+        };
+
+        return match self.clock.now() {
+            Ok(local_time) => match local_time.checked_sub(observed_at) {
+                Some(age) => age >= max_age,
+                // The device clock was set back
+                None => true,
+            },
+            Err(_) => true,
+        };
+    }
+
+    /// The offset is kept until it is measured again, before the next transaction
+    pub fn invalidate(&self) {
+        *self.observed_at.write().unwrap() = None;
+    }
+
+    /// Chain time, the local time until the offset is measured
+    pub fn now(&self) -> Result<Duration, AptosError> {
+        let local_time = match self.clock.now() {
+            Ok(time) => time,
+            Err(error) => return Err(error),
+        };
+
+        let offset_micros = self.offset_micros().unwrap_or(0);
+
+        return match offset_micros >= 0 {
+            true => Ok(local_time + Duration::from_micros(offset_micros as u64)),
+            false => match local_time.checked_sub(Duration::from_micros(offset_micros.unsigned_abs())) {
+                Some(time) => Ok(time),
+                None => Err(AptosError::TimeWentBackwards),
+            },
+        };
+    }
+
+    pub fn now_secs(&self) -> Result<u64, AptosError> {
+        return self.now().map(|time| time.as_secs());
+    }
+}
+
+/// Device clock set by the tests, `None` when it fails
+#[cfg(test)]
+pub struct FakeClock {
+    pub time: std::sync::Arc<RwLock<Option<Duration>>>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    /// Returns the clock and the time it gives, which the test changes
+    pub fn new(secs: u64) -> (Box<dyn Clock>, std::sync::Arc<RwLock<Option<Duration>>>) {
+        let time = std::sync::Arc::new(RwLock::new(Some(Duration::from_secs(secs))));
+
+        return (Box::new(FakeClock { time: time.clone() }), time);
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> Result<Duration, AptosError> {
+        return match *self.time.read().unwrap() {
+            Some(time) => Ok(time),
+            None => Err(AptosError::TimeWentBackwards),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEDGER_SECS: u64 = 1_700_000_000;
+
+    fn fake_clock(secs: u64) -> (Box<dyn Clock>, std::sync::Arc<RwLock<Option<Duration>>>) {
+        return FakeClock::new(secs);
+    }
+
+    fn ledger_timestamp(secs: u64) -> String {
+        return (secs * 1_000_000).to_string();
+    }
+
+    #[test]
+    fn local_time_is_used_until_observed() {
+        let (clock, _) = fake_clock(LEDGER_SECS);
+        let clock = LedgerClock::new(clock);
+
+        assert_eq!(clock.offset_micros(), None);
+        assert_eq!(clock.now_secs().unwrap(), LEDGER_SECS);
+    }
+
+    #[test]
+    fn device_clock_ahead_is_corrected() {
+        let (clock, time) = fake_clock(LEDGER_SECS + 3600);
+        let clock = LedgerClock::new(clock);
+
+        assert_eq!(clock.observe(&ledger_timestamp(LEDGER_SECS)).unwrap(), -3_600_000_000);
+        assert_eq!(clock.now_secs().unwrap(), LEDGER_SECS);
+
+        *time.write().unwrap() = Some(Duration::from_secs(LEDGER_SECS + 3610));
+        assert_eq!(clock.now_secs().unwrap(), LEDGER_SECS + 10);
+    }
+
+    #[test]
+    fn device_clock_behind_is_corrected() {
+        let (clock, _) = fake_clock(LEDGER_SECS - 3600);
+        let clock = LedgerClock::new(clock);
+
+        assert_eq!(clock.observe(&ledger_timestamp(LEDGER_SECS)).unwrap(), 3_600_000_000);
+        assert_eq!(clock.now_secs().unwrap(), LEDGER_SECS);
+    }
+
+    #[test]
+    fn invalid_ledger_timestamp_is_rejected() {
+        let (clock, _) = fake_clock(LEDGER_SECS);
+        let clock = LedgerClock::new(clock);
+
+        assert!(matches!(clock.observe("soon"), Err(AptosError::InvalidResponse)));
+        assert_eq!(clock.offset_micros(), None);
+    }
+
+    #[test]
+    fn time_went_backwards() {
+        let (clock, time) = fake_clock(LEDGER_SECS + 3600);
+        let clock = LedgerClock::new(clock);
+        clock.observe(&ledger_timestamp(LEDGER_SECS)).unwrap();
+
+        // The offset is larger than the local time
+        *time.write().unwrap() = Some(Duration::from_secs(60));
+        assert!(matches!(clock.now(), Err(AptosError::TimeWentBackwards)));
+
+        // The device clock fails
+        *time.write().unwrap() = None;
+        assert!(matches!(clock.now(), Err(AptosError::TimeWentBackwards)));
+        assert!(matches!(clock.observe(&ledger_timestamp(LEDGER_SECS)), Err(AptosError::TimeWentBackwards)));
+    }
+
+    #[test]
+    fn offset_is_measured_again_once_old() {
+        let (clock, time) = fake_clock(LEDGER_SECS + 3600);
+        let clock = LedgerClock::new(clock);
+        let max_age = Duration::from_secs(300);
+
+        assert!(clock.needs_sync(max_age));
+
+        clock.observe(&ledger_timestamp(LEDGER_SECS)).unwrap();
+        assert!(!clock.needs_sync(max_age));
+
+        *time.write().unwrap() = Some(Duration::from_secs(LEDGER_SECS + 3900));
+        assert!(clock.needs_sync(max_age));
+
+        // The device clock set back
+        clock.observe(&ledger_timestamp(LEDGER_SECS + 300)).unwrap();
+        *time.write().unwrap() = Some(Duration::from_secs(LEDGER_SECS));
+        assert!(clock.needs_sync(max_age));
+
+        // Invalidated offsets are still used until measured again
+        *time.write().unwrap() = Some(Duration::from_secs(LEDGER_SECS + 3900));
+        clock.observe(&ledger_timestamp(LEDGER_SECS + 300)).unwrap();
+        clock.invalidate();
+        assert!(clock.needs_sync(max_age));
+        assert_eq!(clock.now_secs().unwrap(), LEDGER_SECS + 300);
+    }
+
+    #[test]
+    fn transactions_expire_with_the_chain_time() {
+        let (clock, time) = fake_clock(LEDGER_SECS + 3600);
+        let rest_client = AptosRestClient::with_clock("http://localhost".to_string(), clock);
+        rest_client.clock().observe(&ledger_timestamp(LEDGER_SECS)).unwrap();
+
+        let address = AccountAddress::parse("0xa").unwrap();
+        let transaction = rest_client.generate_transaction_at(&address, serde_json::json!({}), 0, 1000, 100).unwrap();
+        let transaction: serde_json::Value = serde_json::from_str(&transaction).unwrap();
+
+        assert_eq!(
+            transaction["expiration_timestamp_secs"],
+            (LEDGER_SECS + TRANSACTION_EXPIRATION_SECS).to_string(),
+        );
+
+        *time.write().unwrap() = Some(Duration::from_secs(60));
+        assert!(matches!(
+            rest_client.generate_transaction_at(&address, serde_json::json!({}), 0, 1000, 100),
+            Err(AptosError::TimeWentBackwards),
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::aptos::*;
+
+/// Native coin of the network, the one paying the gas
+pub const APTOS_COIN_TYPE: &str = "0x1::aptos_coin::AptosCoin";
+
+pub fn aptos_coin_type() -> TypeTag {
+    return TypeTag::parse(APTOS_COIN_TYPE).unwrap();
+}
+
+/// Resource holding the balance of coin T, an account can only receive T once it is published
+pub fn coin_store_type(coin_type: &TypeTag) -> StructTag {
+    return StructTag {
+        address: AccountAddress::parse("0x1").unwrap(),
+        module: "coin".to_string(),
+        name: "CoinStore".to_string(),
+        type_params: vec![coin_type.clone()],
+    };
+}
+
+/// Call of `0x1::managed_coin::register<T>` publishing the coin store of the sender
+pub fn register_coin_payload(coin_type: &TypeTag) -> EntryFunction {
+    return EntryFunction::new("0x1::managed_coin::register", vec![coin_type.clone()], Vec::new()).unwrap();
+}
+
+/// Call of `0x1::coin::transfer<T>`, aborts when the recipient has no coin store
+pub fn coin_transfer_payload(coin_type: &TypeTag, address_to: &AccountAddress, amount: u64) -> EntryFunction {
+    let arguments = vec![MoveValue::Address(*address_to), MoveValue::U64(amount)];
+
+    return EntryFunction::new("0x1::coin::transfer", vec![coin_type.clone()], arguments).unwrap();
+}
+
+/// Call of `0x1::aptos_account::transfer_coins<T>`, publishes the coin store of the recipient when missing
+pub fn transfer_coins_payload(coin_type: &TypeTag, address_to: &AccountAddress, amount: u64) -> EntryFunction {
+    let arguments = vec![MoveValue::Address(*address_to), MoveValue::U64(amount)];
+
+    return EntryFunction::new("0x1::aptos_account::transfer_coins", vec![coin_type.clone()], arguments).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOON_COIN_TYPE: &str = "0xcafe::moon_coin::MoonCoin";
+
+    #[test]
+    fn coin_store_is_typed_by_the_coin() {
+        let coin_type = TypeTag::parse(MOON_COIN_TYPE).unwrap();
+
+        assert_eq!(coin_store_type(&coin_type).to_string(), "0x1::coin::CoinStore<0xcafe::moon_coin::MoonCoin>");
+        assert_eq!(coin_store_type(&aptos_coin_type()).to_string(), "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>");
+    }
+
+    #[test]
+    fn register_payload_takes_the_coin_as_type_argument() {
+        let payload = register_coin_payload(&TypeTag::parse(MOON_COIN_TYPE).unwrap()).to_payload();
+
+        assert_eq!(payload["type"], ENTRY_FUNCTION_PAYLOAD_TYPE);
+        assert_eq!(payload["function"], "0x1::managed_coin::register");
+        assert_eq!(payload["type_arguments"], serde_json::json!([MOON_COIN_TYPE]));
+        assert_eq!(payload["arguments"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn transfer_payloads_send_the_amount_to_the_recipient() {
+        let coin_type = TypeTag::parse(MOON_COIN_TYPE).unwrap();
+        let address_to = AccountAddress::parse("0xb").unwrap();
+
+        let coin_transfer = coin_transfer_payload(&coin_type, &address_to, 500).to_payload();
+        let transfer_coins = transfer_coins_payload(&coin_type, &address_to, 500).to_payload();
+
+        assert_eq!(coin_transfer["function"], "0x1::coin::transfer");
+        assert_eq!(transfer_coins["function"], "0x1::aptos_account::transfer_coins");
+
+        for payload in [coin_transfer, transfer_coins] {
+            assert_eq!(payload["type_arguments"], serde_json::json!([MOON_COIN_TYPE]));
+            assert_eq!(payload["arguments"], serde_json::json!([address_to.to_string(), "500"]));
+        }
+    }
+}
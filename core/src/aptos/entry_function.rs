@@ -0,0 +1,298 @@
+use std::convert::TryFrom;
+
+use crate::aptos::*;
+use crate::aptos::bcs::BcsSerializer;
+
+pub const ENTRY_FUNCTION_PAYLOAD_TYPE: &str = "entry_function_payload";
+/// Field of the payload holding the BCS encoding of each argument as built by the core.
+/// It is never signed as is: the signed arguments are encoded from their JSON form and the types of the ABI,
+/// payloads whose field doesn't match them are rejected. It is removed before the transaction is sent to the node.
+pub const ARGUMENTS_BCS_FIELD: &str = "arguments_bcs";
+
+/// Typed argument of an entry function
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveValue {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    /// Little endian
+    U256([u8; 32]),
+    Address(AccountAddress),
+    String(String),
+    /// `vector<u8>`
+    Bytes(Vec<u8>),
+    Vector(Vec<MoveValue>),
+    Option(Option<Box<MoveValue>>),
+}
+
+impl MoveValue {
+    /// Parses a decimal or 0x prefixed hex u256
+    pub fn u256(value: &str) -> Result<Self, AptosError> {
+        let value = value.trim();
+
+        let (digits, radix) = match value.strip_prefix("0x") {
+            Some(digits) => (digits, 16u16),
+            None => (value, 10u16),
+        };
+
+        if digits.is_empty() {
+            log::error!("Invalid u256 {}", value);
+            return Err(AptosError::InvalidArgument);
+        }
+
+        let mut bytes = [0u8; 32];
+
+        for c in digits.chars() {
+            let digit = match c.to_digit(radix as u32) {
+                Some(digit) => digit as u16,
+                None => {
+                    log::error!("Invalid u256 {}", value);
+                    return Err(AptosError::InvalidArgument)
+                },
+            };
+
+            // bytes = bytes * radix + digit
+            let mut carry = digit;
+
+            for byte in bytes.iter_mut() {
+                let product = *byte as u16 * radix + carry;
+                *byte = (product & 0xff) as u8;
+                carry = product >> 8;
+            }
+
+            if carry != 0 {
+                log::error!("u256 overflow {}", value);
+                return Err(AptosError::InvalidArgument);
+            }
+        }
+
+        return Ok(Self::U256(bytes));
+    }
+
+    /// Parses the JSON form of a value of the type, the form given by `to_json`
+    pub fn from_json(type_tag: &TypeTag, value: &serde_json::Value) -> Result<Self, AptosError> {
+        let parsed = match (type_tag, value) {
+            (TypeTag::Bool, serde_json::Value::Bool(value)) => Some(Self::Bool(*value)),
+            (TypeTag::U8, _) => json_unsigned(value).and_then(|value| u8::try_from(value).ok()).map(Self::U8),
+            (TypeTag::U16, _) => json_unsigned(value).and_then(|value| u16::try_from(value).ok()).map(Self::U16),
+            (TypeTag::U32, _) => json_unsigned(value).and_then(|value| u32::try_from(value).ok()).map(Self::U32),
+            (TypeTag::U64, _) => json_unsigned(value).and_then(|value| u64::try_from(value).ok()).map(Self::U64),
+            (TypeTag::U128, _) => json_unsigned(value).map(Self::U128),
+            (TypeTag::U256, serde_json::Value::String(value)) => Self::u256(value).ok(),
+            (TypeTag::Address, serde_json::Value::String(value)) => AccountAddress::parse(value).ok().map(Self::Address),
+            (TypeTag::Vector(element), serde_json::Value::String(value)) if **element == TypeTag::U8 => {
+                hex::decode(value.trim_start_matches("0x")).ok().map(Self::Bytes)
+            },
+            (TypeTag::Vector(element), serde_json::Value::Array(values)) => {
+                let mut elements: Vec<MoveValue> = Vec::new();
+
+                for value in values {
+                    match Self::from_json(element, value) {
+                        Ok(element) => elements.push(element),
+                        Err(error) => return Err(error),
+                    }
+                }
+
+                Some(Self::Vector(elements))
+            },
+            (TypeTag::Struct(struct_tag), serde_json::Value::String(value)) if struct_tag.is_framework("string", "String") => {
+                Some(Self::String(value.clone()))
+            },
+            (TypeTag::Struct(struct_tag), serde_json::Value::Object(_)) if struct_tag.is_framework("option", "Option") => {
+                match (struct_tag.type_params.first(), value["vec"].as_array().map(|values| values.as_slice())) {
+                    (Some(_), Some([])) => Some(Self::Option(None)),
+                    (Some(element), Some([value])) => match Self::from_json(element, value) {
+                        Ok(value) => Some(Self::Option(Some(Box::new(value)))),
+                        Err(error) => return Err(error),
+                    },
+                    _ => None,
+                }
+            },
+            _ => None,
+        };
+
+        return match parsed {
+            Some(parsed) => Ok(parsed),
+            None => {
+                log::error!("Invalid {} argument {}", type_tag, value);
+                Err(AptosError::InvalidArgument)
+            },
+        };
+    }
+
+    /// BCS encoding of the value, before it is prefixed by its length in the payload
+    pub fn serialize(&self, serializer: &mut BcsSerializer) {
+        match self {
+            Self::Bool(value) => { serializer.bool(*value); },
+            Self::U8(value) => { serializer.u8(*value); },
+            Self::U16(value) => { serializer.u16(*value); },
+            Self::U32(value) => { serializer.u32(*value); },
+            Self::U64(value) => { serializer.u64(*value); },
+            Self::U128(value) => { serializer.u128(*value); },
+            Self::U256(value) => { serializer.fixed_bytes(value); },
+            Self::Address(address) => { serializer.fixed_bytes(address.as_bytes()); },
+            Self::String(value) => { serializer.str(value); },
+            Self::Bytes(value) => { serializer.bytes(value); },
+            Self::Vector(values) => {
+                serializer.uleb128(values.len() as u64);
+
+                for value in values {
+                    value.serialize(serializer);
+                }
+            },
+            // An option is a vector of zero or one element
+            Self::Option(value) => match value {
+                Some(value) => {
+                    serializer.uleb128(1);
+                    value.serialize(serializer);
+                },
+                None => { serializer.uleb128(0); },
+            },
+        };
+    }
+
+    pub fn to_bcs(&self) -> Vec<u8> {
+        let mut serializer = BcsSerializer::new();
+        self.serialize(&mut serializer);
+
+        return serializer.finish();
+    }
+
+    /// JSON form expected by the node: 64 bits and larger integers as strings, `vector<u8>` as hex
+    pub fn to_json(&self) -> serde_json::Value {
+        return match self {
+            Self::Bool(value) => serde_json::json!(value),
+            Self::U8(value) => serde_json::json!(value),
+            Self::U16(value) => serde_json::json!(value),
+            Self::U32(value) => serde_json::json!(value),
+            Self::U64(value) => serde_json::json!(value.to_string()),
+            Self::U128(value) => serde_json::json!(value.to_string()),
+            Self::U256(value) => serde_json::json!(u256_to_string(value)),
+            Self::Address(address) => serde_json::json!(address.to_string()),
+            Self::String(value) => serde_json::json!(value),
+            Self::Bytes(value) => serde_json::json!(format!("0x{}", hex::encode(value))),
+            Self::Vector(values) => serde_json::Value::Array(values.iter().map(|value| value.to_json()).collect()),
+            Self::Option(value) => serde_json::json!({
+                "vec": value.iter().map(|value| value.to_json()).collect::<Vec<serde_json::Value>>(),
+            }),
+        };
+    }
+}
+
+/// Call of `address::module::function` with its type arguments and arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryFunction {
+    pub module_address: AccountAddress,
+    pub module_name: String,
+    pub function_name: String,
+    pub type_arguments: Vec<TypeTag>,
+    pub arguments: Vec<MoveValue>,
+}
+
+impl EntryFunction {
+    /// Parses the function id `address::module::function`
+    pub fn new(function_id: &str, type_arguments: Vec<TypeTag>, arguments: Vec<MoveValue>) -> Result<Self, AptosError> {
+        let parts: Vec<&str> = function_id.trim().split("::").collect();
+
+        let is_identifier = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if parts.len() != 3 || !is_identifier(parts[1]) || !is_identifier(parts[2]) {
+            log::error!("Invalid function {}", function_id);
+            return Err(AptosError::InvalidFunction);
+        }
+
+        let module_address = match AccountAddress::parse(parts[0]) {
+            Ok(address) => address,
+            Err(error) => return Err(error),
+        };
+
+        return Ok(Self {
+            module_address: module_address,
+            module_name: parts[1].to_string(),
+            function_name: parts[2].to_string(),
+            type_arguments: type_arguments,
+            arguments: arguments,
+        });
+    }
+
+    pub fn function_id(&self) -> String {
+        return format!("{}::{}::{}", self.module_address.to_short_string(), self.module_name, self.function_name);
+    }
+
+    /// Payload given to `generate_transaction`, with the BCS encoding of the arguments
+    pub fn to_payload(&self) -> serde_json::Value {
+        return serde_json::json!({
+            "type": ENTRY_FUNCTION_PAYLOAD_TYPE,
+            "function": self.function_id(),
+            "type_arguments": self.type_arguments.iter().map(|type_tag| type_tag.to_string()).collect::<Vec<String>>(),
+            "arguments": self.arguments.iter().map(|argument| argument.to_json()).collect::<Vec<serde_json::Value>>(),
+            ARGUMENTS_BCS_FIELD: self.arguments.iter().map(|argument| format!("0x{}", hex::encode(argument.to_bcs()))).collect::<Vec<String>>(),
+        });
+    }
+
+    /// BCS encoding of the entry function without the payload variant
+    pub fn serialize(&self, serializer: &mut BcsSerializer) {
+        serializer
+            .fixed_bytes(self.module_address.as_bytes())
+            .str(&self.module_name)
+            .str(&self.function_name)
+            .uleb128(self.type_arguments.len() as u64);
+
+        for type_argument in &self.type_arguments {
+            type_argument.serialize(serializer);
+        }
+
+        serializer.uleb128(self.arguments.len() as u64);
+
+        for argument in &self.arguments {
+            serializer.bytes(&argument.to_bcs());
+        }
+    }
+}
+
+/// Call of `0x1::code::publish_package_txn` publishing the compiled modules of a package
+pub fn publish_package_payload(package_metadata: &[u8], modules: &[Vec<u8>]) -> EntryFunction {
+    let arguments = vec![
+        MoveValue::Bytes(package_metadata.to_vec()),
+        MoveValue::Vector(modules.iter().map(|module| MoveValue::Bytes(module.clone())).collect()),
+    ];
+
+    return EntryFunction::new("0x1::code::publish_package_txn", Vec::new(), arguments).unwrap();
+}
+
+/// Integers up to u32 are JSON numbers, larger ones are strings
+fn json_unsigned(value: &serde_json::Value) -> Option<u128> {
+    return match value {
+        serde_json::Value::Number(number) => number.as_u64().map(|number| number as u128),
+        serde_json::Value::String(number) => number.parse::<u128>().ok(),
+        _ => None,
+    };
+}
+
+/// Decimal form of a little endian u256
+fn u256_to_string(value: &[u8; 32]) -> String {
+    let mut remaining = *value;
+    let mut digits: Vec<char> = Vec::new();
+
+    while remaining.iter().any(|byte| *byte != 0) {
+        // remaining = remaining / 10, from the most significant byte
+        let mut remainder: u16 = 0;
+
+        for byte in remaining.iter_mut().rev() {
+            let current = (remainder << 8) | *byte as u16;
+            *byte = (current / 10) as u8;
+            remainder = current % 10;
+        }
+
+        digits.push(std::char::from_digit(remainder as u32, 10).unwrap());
+    }
+
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+
+    return digits.iter().rev().collect();
+}
@@ -20,11 +20,11 @@ impl AptosFaucetClient {
     /// This creates an account if it does not exist and mints the specified amount of coins into that account.
     pub async fn fund_account(
         &self, 
-        auth_key: &str, 
+        address: &AccountAddress,
         amount: u64
     ) -> Result<Vec<String>, AptosError> {
         let response = match self.http_client
-            .post(format!("{}/mint?amount={}&auth_key={}", self.url, amount, auth_key))
+            .post(format!("{}/mint?amount={}&auth_key={}", self.url, amount, address.to_hex()))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .send()
             .await {
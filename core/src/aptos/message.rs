@@ -0,0 +1,70 @@
+/// Prefix of every signed message, prevents the signature from being valid as a transaction or for another protocol
+pub const MESSAGE_PREFIX: &str = "APTOS";
+
+/// Arbitrary message signed following the Aptos wallet standard
+/// Specs here https://github.com/aptos-foundation/AIPs/blob/main/aips/aip-62.md#signing-messages
+pub struct SignMessagePayload {
+    pub address: Option<String>,
+    pub application: Option<String>,
+    pub chain_id: Option<u8>,
+    pub message: String,
+    pub nonce: String,
+}
+
+impl SignMessagePayload {
+    /// Returns the message that is actually signed:
+    /// the prefix followed by one `key: value` line per included field
+    pub fn full_message(&self) -> String {
+        let mut lines: Vec<String> = vec![MESSAGE_PREFIX.to_string()];
+
+        if let Some(address) = &self.address {
+            lines.push(format!("address: 0x{}", address.trim_start_matches("0x")));
+        }
+
+        if let Some(application) = &self.application {
+            lines.push(format!("application: {}", application));
+        }
+
+        if let Some(chain_id) = self.chain_id {
+            lines.push(format!("chainId: {}", chain_id));
+        }
+
+        lines.push(format!("message: {}", self.message));
+        lines.push(format!("nonce: {}", self.nonce));
+
+        return lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(address: Option<&str>, application: Option<&str>, chain_id: Option<u8>) -> SignMessagePayload {
+        return SignMessagePayload {
+            address: address.map(|address| address.to_string()),
+            application: application.map(|application| application.to_string()),
+            chain_id: chain_id,
+            message: "Sign in".to_string(),
+            nonce: "42".to_string(),
+        };
+    }
+
+    #[test]
+    fn full_message_has_the_optional_fields_in_order() {
+        let full_message = payload(Some("0xa"), Some("https://example.com"), Some(2)).full_message();
+
+        assert_eq!(full_message, "APTOS\naddress: 0xa\napplication: https://example.com\nchainId: 2\nmessage: Sign in\nnonce: 42");
+    }
+
+    #[test]
+    fn full_message_skips_the_fields_not_included() {
+        assert_eq!(payload(None, None, None).full_message(), "APTOS\nmessage: Sign in\nnonce: 42");
+        assert_eq!(payload(None, None, Some(1)).full_message(), "APTOS\nchainId: 1\nmessage: Sign in\nnonce: 42");
+    }
+
+    #[test]
+    fn address_is_prefixed_once() {
+        assert_eq!(payload(Some("a"), None, None).full_message(), payload(Some("0xa"), None, None).full_message());
+    }
+}
@@ -1,13 +1,46 @@
 mod response;
-mod model;
+mod address;
+mod authentication_key;
+pub mod model;
 mod account;
 mod shared_wallet;
+mod account_signer;
+mod signer;
+mod transaction_bundle;
+mod bcs;
+mod raw_transaction;
+mod transaction_hash;
+mod verification;
+mod message;
+mod type_tag;
+mod entry_function;
+mod module_abi;
+mod coin;
+mod rotation;
+mod clock;
 mod rest_client;
 mod faucet_client;
 
 use response::*;
+pub use response::AptosError;
+pub use model::SignedPayload;
+pub use address::*;
+pub use authentication_key::*;
 use model::*;
 pub use account::*;
 pub use shared_wallet::*;
+pub use account_signer::*;
+pub use signer::*;
+pub use transaction_bundle::*;
+pub use raw_transaction::*;
+pub use transaction_hash::*;
+pub use verification::*;
+pub use message::*;
+pub use type_tag::*;
+pub use entry_function::*;
+pub use module_abi::*;
+pub use coin::*;
+pub use rotation::*;
+pub use clock::*;
 pub use rest_client::*;
 pub use faucet_client::*;
\ No newline at end of file
@@ -8,13 +8,6 @@ pub struct GetAccountResponse {
     pub authentication_key: String,
 }
 
-// Create transaction signing
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateTransactionSigningResponse {
-    pub message: String,
-}
-
 // Transaction
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,11 +16,80 @@ pub struct Transaction {
     pub type_transaction: String,
     pub hash: String,
     pub sequence_number: String,
+    #[serde(default)]
+    pub success: Option<bool>,
+    #[serde(default)]
+    pub vm_status: Option<String>,
+    /// Not set while pending
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Microseconds since the epoch, not set while pending
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub sender: Option<String>,
+    #[serde(default)]
+    pub payload: Option<serde_json::Value>,
+    /// Not set while pending
+    #[serde(default)]
+    pub gas_used: Option<String>,
+    #[serde(default)]
+    pub gas_unit_price: Option<String>,
+}
+
+// Events
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountEvent {
+    pub sequence_number: String,
+    #[serde(rename="type")]
+    pub type_event: String,
+    pub data: serde_json::Value,
+    /// Version of the transaction that emitted the event, not given by older nodes
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+// Account modules
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveModule {
+    pub bytecode: String,
+    #[serde(default)]
+    pub abi: Option<MoveModuleAbi>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveModuleAbi {
+    pub address: String,
+    pub name: String,
+    pub exposed_functions: Vec<MoveFunctionAbi>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveFunctionAbi {
+    pub name: String,
+    pub visibility: String,
+    /// Older nodes only give the `script` visibility
+    #[serde(default)]
+    pub is_entry: bool,
+    pub generic_type_params: Vec<serde_json::Value>,
+    /// Move types, generic parameters are named T0, T1...
+    pub params: Vec<String>,
+}
+
+// Ledger info
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerInfo {
+    pub chain_id: u8,
+    pub ledger_version: String,
+    pub ledger_timestamp: String,
 }
 
 // Sign transaction
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SignedPayload {
     pub public_key: String,
     pub signature: String,
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+use crate::aptos::*;
+
+/// Why an entry function call doesn't match the ABI of its module
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiMismatch {
+    ModuleNotFound,
+    FunctionNotFound,
+    NotAnEntryFunction,
+    TypeArgumentCount { expected: usize, given: usize },
+    ArgumentCount { expected: usize, given: usize },
+    /// Index of the argument in the call, the signer excluded
+    ArgumentType { index: usize, expected: String },
+}
+
+impl fmt::Display for AbiMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::ModuleNotFound => write!(f, "module not found"),
+            Self::FunctionNotFound => write!(f, "function not found in the module"),
+            Self::NotAnEntryFunction => write!(f, "function is not an entry function"),
+            Self::TypeArgumentCount { expected, given } => write!(f, "expected {} type arguments, {} given", expected, given),
+            Self::ArgumentCount { expected, given } => write!(f, "expected {} arguments, {} given", expected, given),
+            Self::ArgumentType { index, expected } => write!(f, "argument {} should be a {}", index, expected),
+        };
+    }
+}
+
+/// ABI of the modules of the accounts called so far, fetched once per account
+pub struct ModuleAbiCache {
+    modules: RwLock<HashMap<(AccountAddress, String), MoveModuleAbi>>,
+    fetched_accounts: RwLock<Vec<AccountAddress>>,
+}
+
+impl ModuleAbiCache {
+    pub fn new() -> Self {
+        return Self {
+            modules: RwLock::new(HashMap::new()),
+            fetched_accounts: RwLock::new(Vec::new()),
+        };
+    }
+
+    /// Returns the ABI of the module, `None` when the account has no such module
+    pub async fn module(
+        &self,
+        rest_client: &AptosRestClient,
+        address: &AccountAddress,
+        name: &str,
+    ) -> Result<Option<MoveModuleAbi>, AptosError> {
+        if !self.fetched_accounts.read().unwrap().contains(address) {
+            let modules = match rest_client.get_account_modules(address).await {
+                Ok(modules) => modules,
+                // Unknown accounts have no modules, other errors are not cached
+                Err(AptosError::NotFound) => Vec::new(),
+                Err(error) => return Err(error),
+            };
+
+            let mut cached_modules = self.modules.write().unwrap();
+
+            for abi in modules.into_iter().filter_map(|module| module.abi) {
+                cached_modules.insert((*address, abi.name.clone()), abi);
+            }
+
+            self.fetched_accounts.write().unwrap().push(*address);
+        }
+
+        return Ok(self.modules.read().unwrap().get(&(*address, name.to_string())).cloned());
+    }
+
+    /// Returns the types of the arguments of the function called by the transaction, from the ABI of its module
+    pub async fn parameter_types(&self, rest_client: &AptosRestClient, transaction: &str) -> Result<Vec<TypeTag>, AptosError> {
+        let transaction_json: serde_json::Value = match serde_json::from_str(transaction) {
+            Ok(json) => json,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidJSON)
+            },
+        };
+
+        let entry_function = match payload_function(&transaction_json["payload"]) {
+            Ok(entry_function) => entry_function,
+            Err(error) => return Err(error),
+        };
+
+        let module = match self.module(rest_client, &entry_function.module_address, &entry_function.module_name).await {
+            Ok(module) => module,
+            Err(error) => return Err(error),
+        };
+
+        return match parameter_types(module.as_ref(), &entry_function.function_name, &entry_function.type_arguments) {
+            Ok(parameter_types) => Ok(parameter_types),
+            Err(error) => {
+                log::error!("Invalid call of {}: {}", entry_function.function_id(), error);
+                Err(AptosError::InvalidFunction)
+            },
+        };
+    }
+
+    /// Forgets the modules of the account, they are fetched again on the next call
+    pub fn invalidate(&self, address: &AccountAddress) {
+        self.modules.write().unwrap().retain(|(module_address, _), _| module_address != address);
+        self.fetched_accounts.write().unwrap().retain(|fetched_address| fetched_address != address);
+    }
+}
+
+/// Checks the call against the ABI of its module: the function is an entry function
+/// and the type arguments and arguments match its signature
+pub fn validate_entry_function(module: Option<&MoveModuleAbi>, entry_function: &EntryFunction) -> Result<(), AbiMismatch> {
+    let parameter_types = match parameter_types(module, &entry_function.function_name, &entry_function.type_arguments) {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    if parameter_types.len() != entry_function.arguments.len() {
+        return Err(AbiMismatch::ArgumentCount {
+            expected: parameter_types.len(),
+            given: entry_function.arguments.len(),
+        });
+    }
+
+    for (index, (parameter_type, argument)) in parameter_types.iter().zip(entry_function.arguments.iter()).enumerate() {
+        if !value_matches(parameter_type, argument) {
+            return Err(AbiMismatch::ArgumentType {
+                index: index,
+                expected: parameter_type.to_string(),
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+/// Returns the types of the arguments of the entry function, the signer excluded and the generic parameters replaced
+/// by the type arguments of the call
+pub fn parameter_types(module: Option<&MoveModuleAbi>, function_name: &str, type_arguments: &[TypeTag]) -> Result<Vec<TypeTag>, AbiMismatch> {
+    let module = match module {
+        Some(module) => module,
+        None => return Err(AbiMismatch::ModuleNotFound),
+    };
+
+    let function = match module.exposed_functions.iter().find(|f| f.name == function_name) {
+        Some(function) => function,
+        None => return Err(AbiMismatch::FunctionNotFound),
+    };
+
+    if !function.is_entry && function.visibility != "script" {
+        return Err(AbiMismatch::NotAnEntryFunction);
+    }
+
+    if function.generic_type_params.len() != type_arguments.len() {
+        return Err(AbiMismatch::TypeArgumentCount {
+            expected: function.generic_type_params.len(),
+            given: type_arguments.len(),
+        });
+    }
+
+    // The signer is given by the transaction, not by the arguments
+    let params: Vec<&String> = function.params.iter()
+        .skip_while(|param| param.trim_start_matches('&') == "signer")
+        .collect();
+
+    let mut parameter_types: Vec<TypeTag> = Vec::new();
+
+    for (index, param) in params.iter().enumerate() {
+        let param_type = substitute_generics(param, type_arguments);
+
+        match TypeTag::validate(&param_type) {
+            Ok(parameter_type) => parameter_types.push(parameter_type),
+            Err(_) => return Err(AbiMismatch::ArgumentType {
+                index: index,
+                expected: param_type,
+            }),
+        }
+    }
+
+    return Ok(parameter_types);
+}
+
+fn value_matches(type_tag: &TypeTag, value: &MoveValue) -> bool {
+    return match (type_tag, value) {
+        (TypeTag::Bool, MoveValue::Bool(_)) => true,
+        (TypeTag::U8, MoveValue::U8(_)) => true,
+        (TypeTag::U16, MoveValue::U16(_)) => true,
+        (TypeTag::U32, MoveValue::U32(_)) => true,
+        (TypeTag::U64, MoveValue::U64(_)) => true,
+        (TypeTag::U128, MoveValue::U128(_)) => true,
+        (TypeTag::U256, MoveValue::U256(_)) => true,
+        (TypeTag::Address, MoveValue::Address(_)) => true,
+        (TypeTag::Vector(element), MoveValue::Bytes(_)) => **element == TypeTag::U8,
+        (TypeTag::Vector(element), MoveValue::Vector(values)) => values.iter().all(|value| value_matches(element, value)),
+        (TypeTag::Struct(struct_tag), MoveValue::String(_)) => struct_tag.is_framework("string", "String"),
+        (TypeTag::Struct(struct_tag), MoveValue::Option(value)) => {
+            struct_tag.is_framework("option", "Option")
+                && match (struct_tag.type_params.first(), value) {
+                    (Some(element), Some(value)) => value_matches(element, value),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+        },
+        _ => false,
+    };
+}
+
+/// Replaces the generic parameters T0, T1... of a parameter type by the type arguments of the call,
+/// names after `::` are struct or module names and are kept
+fn substitute_generics(param: &str, type_arguments: &[TypeTag]) -> String {
+    let param = param.trim_start_matches('&').trim_start_matches("mut ");
+
+    let mut output = String::new();
+    let mut identifier = String::new();
+
+    for c in param.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            identifier.push(c);
+            continue;
+        }
+
+        let is_generic = identifier.len() > 1
+            && identifier.starts_with('T')
+            && identifier[1..].chars().all(|digit| digit.is_ascii_digit())
+            && !output.ends_with("::");
+
+        let type_argument = match is_generic {
+            true => identifier[1..].parse::<usize>().ok().and_then(|index| type_arguments.get(index)),
+            false => None,
+        };
+
+        match type_argument {
+            Some(type_argument) => output.push_str(&type_argument.to_string()),
+            None => output.push_str(&identifier),
+        }
+
+        identifier.clear();
+        output.push(c);
+    }
+
+    return output.trim_end().to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin_module() -> MoveModuleAbi {
+        return serde_json::from_value(serde_json::json!({
+            "address": "0x1",
+            "name": "coin",
+            "exposed_functions": [
+                {
+                    "name": "transfer",
+                    "visibility": "public",
+                    "is_entry": true,
+                    "generic_type_params": [{"constraints": []}],
+                    "params": ["&signer", "address", "u64"],
+                },
+                {
+                    "name": "balance",
+                    "visibility": "public",
+                    "is_entry": false,
+                    "generic_type_params": [{"constraints": []}],
+                    "params": ["address"],
+                },
+                {
+                    "name": "deposit_all",
+                    "visibility": "script",
+                    "generic_type_params": [{"constraints": []}],
+                    "params": ["&signer", "vector<0x1::coin::Coin<T0>>", "0x1::option::Option<0x1::string::String>"],
+                },
+            ],
+        })).unwrap();
+    }
+
+    fn call(function_name: &str, type_arguments: Vec<TypeTag>, arguments: Vec<MoveValue>) -> EntryFunction {
+        return EntryFunction::new(&format!("0x1::coin::{}", function_name), type_arguments, arguments).unwrap();
+    }
+
+    #[test]
+    fn accepts_a_matching_call() {
+        let module = coin_module();
+        let transfer = call("transfer", vec![aptos_coin_type()], vec![MoveValue::Address(AccountAddress::parse("0xa").unwrap()), MoveValue::U64(1)]);
+
+        assert_eq!(validate_entry_function(Some(&module), &transfer), Ok(()));
+        assert_eq!(parameter_types(Some(&module), "transfer", &[aptos_coin_type()]), Ok(vec![TypeTag::Address, TypeTag::U64]));
+    }
+
+    #[test]
+    fn generic_parameters_are_replaced_by_the_type_arguments() {
+        let parameter_types = parameter_types(Some(&coin_module()), "deposit_all", &[aptos_coin_type()]).unwrap();
+        let parameter_types: Vec<String> = parameter_types.iter().map(|parameter_type| parameter_type.to_string()).collect();
+
+        assert_eq!(parameter_types, vec![
+            "vector<0x1::coin::Coin<0x1::aptos_coin::AptosCoin>>",
+            "0x1::option::Option<0x1::string::String>",
+        ]);
+    }
+
+    #[test]
+    fn rejects_a_wrong_number_of_arguments() {
+        let transfer = call("transfer", vec![aptos_coin_type()], vec![MoveValue::U64(1)]);
+
+        assert_eq!(validate_entry_function(Some(&coin_module()), &transfer), Err(AbiMismatch::ArgumentCount { expected: 2, given: 1 }));
+    }
+
+    #[test]
+    fn rejects_a_wrong_number_of_type_arguments() {
+        let transfer = call("transfer", vec![], vec![MoveValue::Address(AccountAddress::parse("0xa").unwrap()), MoveValue::U64(1)]);
+
+        assert_eq!(validate_entry_function(Some(&coin_module()), &transfer), Err(AbiMismatch::TypeArgumentCount { expected: 1, given: 0 }));
+    }
+
+    #[test]
+    fn rejects_an_argument_of_another_type() {
+        let transfer = call("transfer", vec![aptos_coin_type()], vec![MoveValue::U64(1), MoveValue::U64(1)]);
+
+        assert_eq!(
+            validate_entry_function(Some(&coin_module()), &transfer),
+            Err(AbiMismatch::ArgumentType { index: 0, expected: "address".to_string() }),
+        );
+    }
+
+    #[test]
+    fn rejects_functions_that_are_not_entry_functions() {
+        let module = coin_module();
+        let balance = call("balance", vec![aptos_coin_type()], vec![MoveValue::Address(AccountAddress::parse("0xa").unwrap())]);
+
+        assert_eq!(validate_entry_function(Some(&module), &balance), Err(AbiMismatch::NotAnEntryFunction));
+        assert_eq!(validate_entry_function(Some(&module), &call("mint", vec![], vec![])), Err(AbiMismatch::FunctionNotFound));
+        assert_eq!(validate_entry_function(None, &balance), Err(AbiMismatch::ModuleNotFound));
+    }
+}
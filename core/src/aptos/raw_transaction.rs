@@ -0,0 +1,323 @@
+use crate::aptos::*;
+use crate::aptos::bcs::BcsSerializer;
+
+const RAW_TRANSACTION_SALT: &[u8] = b"APTOS::RawTransaction";
+const ENTRY_FUNCTION_PAYLOAD_VARIANT: u64 = 2;
+
+/// Returns the message to sign for a raw transaction produced by `generate_transaction`,
+/// computed locally as sha3_256("APTOS::RawTransaction") || bcs(raw_transaction)
+/// The arguments are encoded from their JSON form with the types of the parameters of the called function.
+pub fn signing_message(transaction: &str, chain_id: u8, parameter_types: &[TypeTag]) -> Result<Vec<u8>, AptosError> {
+    let raw_transaction = match raw_transaction_bcs(transaction, chain_id, parameter_types) {
+        Ok(bytes) => bytes,
+        Err(error) => return Err(error),
+    };
+
+    let mut message = sha3_256(RAW_TRANSACTION_SALT).to_vec();
+    message.extend_from_slice(&raw_transaction);
+
+    return Ok(message);
+}
+
+/// BCS encoding of the raw transaction
+/// Specs here https://aptos.dev/guides/creating-a-signed-transaction
+pub fn raw_transaction_bcs(transaction: &str, chain_id: u8, parameter_types: &[TypeTag]) -> Result<Vec<u8>, AptosError> {
+    let transaction_json: serde_json::Value = match serde_json::from_str(transaction) {
+        Ok(json) => json,
+        Err(error) => {
+            log::error!("{}", error);
+            return Err(AptosError::InvalidJSON)
+        },
+    };
+
+    let sender = match AccountAddress::parse(transaction_json["sender"].as_str().unwrap_or("")) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let mut numbers: Vec<u64> = Vec::new();
+
+    for field in ["sequence_number", "max_gas_amount", "gas_unit_price", "expiration_timestamp_secs"].iter() {
+        match json_u64(&transaction_json[*field]) {
+            Some(number) => numbers.push(number),
+            None => {
+                log::error!("Invalid {} in transaction", field);
+                return Err(AptosError::InvalidTransaction)
+            },
+        }
+    }
+
+    let entry_function = match payload_entry_function(&transaction_json["payload"], parameter_types) {
+        Ok(entry_function) => entry_function,
+        Err(error) => return Err(error),
+    };
+
+    let mut serializer = BcsSerializer::new();
+
+    serializer
+        .fixed_bytes(sender.as_bytes())
+        .u64(numbers[0])
+        .uleb128(ENTRY_FUNCTION_PAYLOAD_VARIANT);
+
+    entry_function.serialize(&mut serializer);
+
+    serializer
+        .u64(numbers[1])
+        .u64(numbers[2])
+        .u64(numbers[3])
+        .u8(chain_id);
+
+    return Ok(serializer.finish());
+}
+
+/// Returns the function and type arguments called by the payload, without its arguments.
+/// Only entry function payloads are supported.
+pub fn payload_function(payload: &serde_json::Value) -> Result<EntryFunction, AptosError> {
+    if payload["type"].as_str() != Some(ENTRY_FUNCTION_PAYLOAD_TYPE) {
+        log::error!("Unsupported payload {}", payload["type"]);
+        return Err(AptosError::UnsupportedPayload);
+    }
+
+    let mut type_arguments: Vec<TypeTag> = Vec::new();
+
+    for type_argument in payload["type_arguments"].as_array().cloned().unwrap_or_default() {
+        match TypeTag::parse(type_argument.as_str().unwrap_or("")) {
+            Ok(type_tag) => type_arguments.push(type_tag),
+            Err(error) => return Err(error),
+        }
+    }
+
+    return match EntryFunction::new(payload["function"].as_str().unwrap_or(""), type_arguments, Vec::new()) {
+        Ok(entry_function) => Ok(entry_function),
+        Err(_) => Err(AptosError::InvalidTransaction),
+    };
+}
+
+/// Returns the call of the payload, its arguments parsed from their JSON form with the types of the parameters.
+/// The encoding of the arguments kept by the core, when there is one, must be the one of the JSON arguments.
+pub fn payload_entry_function(payload: &serde_json::Value, parameter_types: &[TypeTag]) -> Result<EntryFunction, AptosError> {
+    let mut entry_function = match payload_function(payload) {
+        Ok(entry_function) => entry_function,
+        Err(error) => return Err(error),
+    };
+
+    let arguments = payload["arguments"].as_array().cloned().unwrap_or_default();
+
+    if arguments.len() != parameter_types.len() {
+        log::error!("{} takes {} arguments, {} given", entry_function.function_id(), parameter_types.len(), arguments.len());
+        return Err(AptosError::InvalidTransaction);
+    }
+
+    for (parameter_type, argument) in parameter_types.iter().zip(arguments.iter()) {
+        match MoveValue::from_json(parameter_type, argument) {
+            Ok(value) => entry_function.arguments.push(value),
+            Err(error) => return Err(error),
+        }
+    }
+
+    if let Some(arguments_bcs) = payload.get(ARGUMENTS_BCS_FIELD) {
+        let expected: Vec<serde_json::Value> = entry_function.arguments.iter()
+            .map(|argument| serde_json::json!(format!("0x{}", hex::encode(argument.to_bcs()))))
+            .collect();
+
+        if arguments_bcs.as_array() != Some(&expected) {
+            log::error!("The encoding of the arguments of {} doesn't match their JSON form", entry_function.function_id());
+            return Err(AptosError::InvalidTransaction);
+        }
+    }
+
+    return Ok(entry_function);
+}
+
+/// Transaction as sent to the node, without the fields only kept by the core
+pub fn node_transaction(transaction: &str) -> Result<serde_json::Value, AptosError> {
+    let mut transaction_json: serde_json::Value = match serde_json::from_str(transaction) {
+        Ok(json) => json,
+        Err(error) => {
+            log::error!("{}", error);
+            return Err(AptosError::InvalidJSON)
+        },
+    };
+
+    match transaction_json["payload"].as_object_mut() {
+        Some(payload) => { payload.remove(ARGUMENTS_BCS_FIELD); },
+        None => {
+            log::error!("Transaction without payload");
+            return Err(AptosError::InvalidTransaction)
+        },
+    };
+
+    return Ok(transaction_json);
+}
+
+/// Numbers are strings in the JSON transactions
+fn json_u64(value: &serde_json::Value) -> Option<u64> {
+    return match value {
+        serde_json::Value::String(s) => s.parse::<u64>().ok(),
+        serde_json::Value::Number(n) => n.as_u64(),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: &str = "0x0a550c18";
+    const RECIPIENT: &str = "0xdd";
+
+    /// Transaction as returned by `generate_transaction` for the payload
+    fn transaction(payload: serde_json::Value) -> String {
+        return serde_json::json!({
+            "sender": SENDER,
+            "sequence_number": "7",
+            "max_gas_amount": "1000",
+            "gas_unit_price": "1",
+            "expiration_timestamp_secs": "1660000000",
+            "payload": payload,
+        }).to_string();
+    }
+
+    fn transfer_types() -> Vec<TypeTag> {
+        return vec![TypeTag::Address, TypeTag::U64];
+    }
+
+    fn transfer() -> String {
+        let arguments = vec![MoveValue::Address(AccountAddress::parse(RECIPIENT).unwrap()), MoveValue::U64(100)];
+
+        return transaction(EntryFunction::new("0x1::coin::transfer", Vec::new(), arguments).unwrap().to_payload());
+    }
+
+    /// Field by field encoding of `transfer`, following the RawTransaction layout of the node
+    fn expected_transfer(chain_id: u8) -> String {
+        return [
+            // sender
+            format!("{:0>64}", "0a550c18"),
+            // sequence number
+            "0700000000000000".to_string(),
+            // entry function payload variant
+            "02".to_string(),
+            // module address, module name and function name
+            format!("{:0>64}", "1"),
+            "04636f696e".to_string(),
+            "087472616e73666572".to_string(),
+            // no type arguments
+            "00".to_string(),
+            // two arguments, each one prefixed by its length
+            "02".to_string(),
+            format!("20{:0>64}", "dd"),
+            "086400000000000000".to_string(),
+            // max gas amount, gas unit price and expiration
+            "e803000000000000".to_string(),
+            "0100000000000000".to_string(),
+            "0097f16200000000".to_string(),
+            format!("{:02x}", chain_id),
+        ].concat();
+    }
+
+    #[test]
+    fn signing_message_is_prefixed_by_the_raw_transaction_salt() {
+        let message = signing_message(&transfer(), 4, &transfer_types()).unwrap();
+
+        // sha3_256("APTOS::RawTransaction")
+        assert_eq!(hex::encode(&message[..32]), "b5e97db07fa0bd0e5598aa3643a9bc6f6693bddc1a9fec9e674a461eaa00b193");
+        assert_eq!(hex::encode(&message[32..]), expected_transfer(4));
+    }
+
+    #[test]
+    fn arguments_are_encoded_from_their_type() {
+        let arguments = vec![
+            MoveValue::U8(1),
+            MoveValue::U16(2),
+            MoveValue::U32(3),
+            MoveValue::U128(4),
+            MoveValue::u256("5").unwrap(),
+            MoveValue::Bytes(vec![0xab, 0xcd]),
+            MoveValue::String("aptos".to_string()),
+            MoveValue::Option(Some(Box::new(MoveValue::U64(6)))),
+            MoveValue::Option(None),
+            MoveValue::Vector(vec![MoveValue::Address(AccountAddress::parse(RECIPIENT).unwrap())]),
+            MoveValue::Vector(Vec::new()),
+        ];
+
+        let parameter_types: Vec<TypeTag> = [
+            "u8", "u16", "u32", "u128", "u256", "vector<u8>", "0x1::string::String", "0x1::option::Option<u64>",
+            "0x1::option::Option<u64>", "vector<address>", "vector<u64>",
+        ].iter().map(|parameter_type| TypeTag::parse(parameter_type).unwrap()).collect();
+
+        let entry_function = EntryFunction::new("0x1::test::call", vec![aptos_coin_type()], arguments).unwrap();
+
+        let mut serializer = BcsSerializer::new();
+        serializer.uleb128(ENTRY_FUNCTION_PAYLOAD_VARIANT);
+        entry_function.serialize(&mut serializer);
+
+        let raw_transaction = raw_transaction_bcs(&transaction(entry_function.to_payload()), 1, &parameter_types).unwrap();
+        let payload = hex::encode(serializer.finish());
+
+        assert_eq!(hex::encode(&raw_transaction[40..40 + payload.len() / 2]), payload);
+
+        // Each argument prefixed by its length, numbers are encoded with the width of their type
+        let arguments = [
+            "0101", "020200", "0403000000", "1004000000000000000000000000000000",
+            &format!("2005{:0<62}", ""), "0302abcd", "06056170746f73", "09010600000000000000",
+            "0100", &format!("2101{:0>64}", "dd"), "0100",
+        ].concat();
+
+        assert!(payload.ends_with(&format!("0b{}", arguments)));
+    }
+
+    #[test]
+    fn chain_id_is_signed() {
+        assert_ne!(signing_message(&transfer(), 1, &transfer_types()).unwrap(), signing_message(&transfer(), 2, &transfer_types()).unwrap());
+    }
+
+    #[test]
+    fn node_transaction_has_no_argument_encoding() {
+        let transaction = node_transaction(&transfer()).unwrap();
+
+        assert!(transaction["payload"].get(ARGUMENTS_BCS_FIELD).is_none());
+        assert_eq!(transaction["payload"]["arguments"], serde_json::json!([format!("0x{:0>64}", "dd"), "100"]));
+    }
+
+    #[test]
+    fn arguments_are_signed_from_their_json_form() {
+        // Payloads built by the host have no encoding of their arguments
+        let mut transaction: serde_json::Value = serde_json::from_str(&transfer()).unwrap();
+        transaction["payload"].as_object_mut().unwrap().remove(ARGUMENTS_BCS_FIELD);
+
+        assert_eq!(
+            signing_message(&transaction.to_string(), 4, &transfer_types()).unwrap(),
+            signing_message(&transfer(), 4, &transfer_types()).unwrap(),
+        );
+
+        // The encoding of the amount doesn't match the amount shown
+        let mut transaction: serde_json::Value = serde_json::from_str(&transfer()).unwrap();
+        transaction["payload"][ARGUMENTS_BCS_FIELD][1] = serde_json::json!("0xe803000000000000");
+
+        assert!(matches!(signing_message(&transaction.to_string(), 4, &transfer_types()), Err(AptosError::InvalidTransaction)));
+
+        let mut transaction: serde_json::Value = serde_json::from_str(&transfer()).unwrap();
+        transaction["payload"]["arguments"][1] = serde_json::json!("1000");
+
+        assert!(matches!(signing_message(&transaction.to_string(), 4, &transfer_types()), Err(AptosError::InvalidTransaction)));
+
+        // Arguments that don't fit the types of the function
+        assert!(matches!(signing_message(&transfer(), 4, &[TypeTag::Address]), Err(AptosError::InvalidTransaction)));
+        assert!(matches!(signing_message(&transfer(), 4, &[TypeTag::Address, TypeTag::Bool]), Err(AptosError::InvalidArgument)));
+    }
+
+    #[test]
+    fn rejects_unsupported_transactions() {
+        assert!(matches!(raw_transaction_bcs("{", 1, &[]), Err(AptosError::InvalidJSON)));
+
+        let mut transaction: serde_json::Value = serde_json::from_str(&transfer()).unwrap();
+        transaction["payload"]["type"] = serde_json::json!("script_function_payload");
+
+        assert!(matches!(raw_transaction_bcs(&transaction.to_string(), 1, &transfer_types()), Err(AptosError::UnsupportedPayload)));
+
+        let mut transaction: serde_json::Value = serde_json::from_str(&transfer()).unwrap();
+        transaction["sequence_number"] = serde_json::json!("-1");
+
+        assert!(matches!(raw_transaction_bcs(&transaction.to_string(), 1, &transfer_types()), Err(AptosError::InvalidTransaction)));
+    }
+}
@@ -3,14 +3,48 @@ pub enum AptosError {
     InvalidJSON,
     InvalidRequest,
     InvalidResponse,
+    InvalidKeypair,
+    UnknownSigner,
+    NotEnoughSignatures,
+    InvalidKeystore,
+    SigningFailed,
+    InvalidBundle,
+    BundleMismatch,
+    InvalidTransaction,
+    UnsupportedPayload,
+    InvalidAddress,
+    InvalidSignature,
+    UnknownAccount,
+    StorageFailed,
+    UnknownContact,
+    ContactExists,
+    InvalidTypeTag,
+    InvalidArgument,
+    InvalidFunction,
+    RecipientNotRegistered,
+    UnknownTransaction,
+    InvalidGasPrice,
+    InvalidThreshold,
+    TooManyPublicKeys,
+    InvalidPassword,
+    InvalidChainId,
+    /// The node answered 404, the account, resource or transaction doesn't exist
+    NotFound,
 
     InvalidSequenceNumber,
     TimeWentBackwards,
+    TransactionTimeout,
 }
 
 pub async fn handle_response<T: serde::de::DeserializeOwned>(
     response: reqwest::Response,
 ) -> Result<T, AptosError> {
+    // Whatever its body, a 404 means that the account, resource or transaction doesn't exist
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        log::info!("Not found: {}", response.url());
+        return Err(AptosError::NotFound);
+    }
+
     let json: serde_json::Value = match response.json().await {
         Ok(res) => res,
         Err(error) => {
@@ -1,28 +1,144 @@
 use core::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 
 use crate::aptos::*;
 
+// Number of seconds to wait for a transaction to be committed
+const TRANSACTION_WAIT_ATTEMPTS: u32 = 60;
+// Gas limit of the transactions unless given
+pub const DEFAULT_MAX_GAS_AMOUNT: u64 = 1000;
+pub const DEFAULT_GAS_UNIT_PRICE: u64 = 1;
+// Handle of the coin store emitting an event for each deposit
+const DEPOSIT_EVENTS_FIELD: &str = "deposit_events";
+// Number of seconds a transaction can wait in the mempool before it expires
+pub const TRANSACTION_EXPIRATION_SECS: u64 = 600;
+// Maximum number of transactions returned by the node in one page
+pub const TRANSACTIONS_PAGE_LIMIT: u16 = 100;
+// Age after which the clock offset and the chain id are fetched again with the ledger info
+const LEDGER_SYNC_INTERVAL: Duration = Duration::from_secs(300);
+
 #[derive(Clone)]
 pub struct AptosRestClient {
     url: String,
     http_client: reqwest::Client,
+    clock: Arc<LedgerClock>,
+    /// Chain id of the last ledger info, 0 until known
+    chain_id: Arc<AtomicU8>,
+    /// Set when the node reports another chain id than before, until taken
+    chain_changed: Arc<AtomicBool>,
 }
 
 impl AptosRestClient {
     pub fn new(url: String) -> Self {
+        return Self::with_clock(url, Box::new(SystemClock));
+    }
+
+    /// Same as `new` with the local clock given, expirations are computed with it corrected by the ledger timestamp
+    pub fn with_clock(url: String, clock: Box<dyn Clock>) -> Self {
         let http_client = reqwest::Client::new();
 
         return Self {
             url: url,
             http_client: http_client,
+            clock: Arc::new(LedgerClock::new(clock)),
+            chain_id: Arc::new(AtomicU8::new(0)),
+            chain_changed: Arc::new(AtomicBool::new(false)),
+        };
+    }
+
+    /// REST URL of the node
+    pub fn url(&self) -> &str {
+        return &self.url;
+    }
+
+    /// Clock corrected by the ledger timestamp of the last ledger info
+    pub fn clock(&self) -> &LedgerClock {
+        return &self.clock;
+    }
+
+    /// Measures the offset of the local clock to the chain, returns it in microseconds
+    pub async fn sync_clock(&self) -> Result<i64, AptosError> {
+        let ledger_info = match self.get_ledger_info().await {
+            Ok(ledger_info) => ledger_info,
+            Err(error) => return Err(error),
+        };
+
+        return self.clock.observe(&ledger_info.ledger_timestamp);
+    }
+
+    /// Measures the offset of the local clock unless measured recently, transaction expirations are computed with the chain time.
+    /// The chain id is refreshed with it.
+    pub async fn ensure_clock_synced(&self) -> Result<(), AptosError> {
+        if !self.clock.needs_sync(LEDGER_SYNC_INTERVAL) {
+            return Ok(());
+        }
+
+        return self.sync_clock().await.map(|_| ());
+    }
+
+    /// Chain id signed in the transactions, the one of the last ledger info
+    pub async fn chain_id(&self) -> Result<u8, AptosError> {
+        let chain_id = self.chain_id.load(Ordering::SeqCst);
+
+        if chain_id != 0 {
+            return Ok(chain_id);
+        }
+
+        return match self.get_ledger_info().await {
+            Ok(ledger_info) => Ok(ledger_info.chain_id),
+            Err(error) => Err(error),
         };
     }
 
+    /// Returns the new chain id once after the node reported another chain than before
+    pub fn take_chain_change(&self) -> Option<u8> {
+        if !self.chain_changed.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+
+        return Some(self.chain_id.load(Ordering::SeqCst));
+    }
+
+    /// Returns the chain id, the latest ledger version and its timestamp, which corrects the clock
+    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_ledger_info
+    pub async fn get_ledger_info(&self) -> Result<LedgerInfo, AptosError> {
+        let response = match self.http_client
+            .get(format!("{}/", self.url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .send()
+            .await {
+                Ok(res) => res,
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidRequest)
+                },
+            };
+
+        let ledger_info = match handle_response::<LedgerInfo>(response).await {
+            Ok(ledger_info) => ledger_info,
+            Err(error) => return Err(error),
+        };
+
+        if let Err(error) = self.clock.observe(&ledger_info.ledger_timestamp) {
+            log::warn!("Clock not synced: {:?}", error);
+        }
+
+        let previous_chain_id = self.chain_id.swap(ledger_info.chain_id, Ordering::SeqCst);
+
+        if previous_chain_id != 0 && previous_chain_id != ledger_info.chain_id {
+            log::warn!("Node {} switched from chain {} to chain {}", self.url, previous_chain_id, ledger_info.chain_id);
+            self.chain_changed.store(true, Ordering::SeqCst);
+        }
+
+        return Ok(ledger_info);
+    }
+
     /// Returns the sequence number and authentication key for an account
     /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_account
     pub async fn get_account(
         &self, 
-        account_address: &str
+        account_address: &AccountAddress
     ) -> Result<GetAccountResponse, AptosError> {
         let response = match self.http_client
             .get(format!("{}/accounts/{}", self.url, account_address))
@@ -39,15 +155,15 @@ impl AptosRestClient {
         return handle_response::<GetAccountResponse>(response).await;
     }
 
-    /// Returns all resources associated with the account
-    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_account_resources
+    /// Returns a resource of the account
+    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_account_resource
     pub async fn get_account_resource(
         &self, 
-        account_address: &str, 
-        resource_type: &str
+        account_address: &AccountAddress,
+        resource_type: &StructTag
     ) -> Result<serde_json::Value, AptosError> {
         let response = match self.http_client
-            .get(format!("{}/accounts/{}/resource/{}", self.url, account_address, resource_type))
+            .get(format!("{}/accounts/{}/resource/{}", self.url, account_address, resource_type.to_url_path()))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .send()
             .await {
@@ -61,14 +177,148 @@ impl AptosRestClient {
         return handle_response::<serde_json::Value>(response).await;
     }
 
+    /// Returns the modules published by the account with their ABI
+    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_account_modules
+    pub async fn get_account_modules(
+        &self,
+        account_address: &AccountAddress
+    ) -> Result<Vec<MoveModule>, AptosError> {
+        let response = match self.http_client
+            .get(format!("{}/accounts/{}/modules", self.url, account_address))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .send()
+            .await {
+                Ok(res) => res,
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidRequest)
+                },
+            };
+
+        return handle_response::<Vec<MoveModule>>(response).await;
+    }
+
+    /// Returns true when the account has published the coin store of the coin and can receive it
+    pub async fn is_coin_registered(
+        &self,
+        account_address: &AccountAddress,
+        coin_type: &TypeTag
+    ) -> Result<bool, AptosError> {
+        return match self.get_account_resource(account_address, &coin_store_type(coin_type)).await {
+            Ok(_) => Ok(true),
+            // Only a missing resource means that the coin isn't registered, other errors are not an answer
+            Err(AptosError::NotFound) => Ok(false),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Returns the AptosCoin balance of an account
+    pub async fn get_account_balance(
+        &self,
+        account_address: &AccountAddress
+    ) -> Result<u64, AptosError> {
+        let resource_type = coin_store_type(&aptos_coin_type());
+
+        let resource = match self.get_account_resource(account_address, &resource_type).await {
+            Ok(resource) => resource,
+            Err(error) => return Err(error),
+        };
+
+        return match resource["data"]["coin"]["value"].as_str().and_then(|s| s.parse::<u64>().ok()) {
+            Some(balance) => Ok(balance),
+            None => Err(AptosError::InvalidResponse),
+        };
+    }
+
+    /// Returns the events of a handle held in a resource of the account, starting at a sequence number, in order
+    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_events_by_event_handle
+    pub async fn get_account_events(
+        &self,
+        account_address: &AccountAddress,
+        event_handle: &StructTag,
+        field_name: &str,
+        start: u64,
+        limit: u16,
+    ) -> Result<Vec<AccountEvent>, AptosError> {
+        let response = match self.http_client
+            .get(format!("{}/accounts/{}/events/{}/{}", self.url, account_address, event_handle.to_url_path(), field_name))
+            .query(&[("start", start.to_string()), ("limit", limit.to_string())])
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .send()
+            .await {
+                Ok(res) => res,
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidRequest)
+                },
+            };
+
+        return handle_response::<Vec<AccountEvent>>(response).await;
+    }
+
+    /// Returns the number of events emitted on a handle, which is the sequence number of the next one
+    pub async fn get_account_event_count(
+        &self,
+        account_address: &AccountAddress,
+        event_handle: &StructTag,
+        field_name: &str,
+    ) -> Result<u64, AptosError> {
+        let resource = match self.get_account_resource(account_address, event_handle).await {
+            Ok(resource) => resource,
+            Err(error) => return Err(error),
+        };
+
+        return match resource["data"][field_name]["counter"].as_str().and_then(|s| s.parse::<u64>().ok()) {
+            Some(count) => Ok(count),
+            None => Err(AptosError::InvalidResponse),
+        };
+    }
+
+    /// Returns the AptosCoin deposits of an account, starting at an event sequence number.
+    /// The `amount` of each deposit is in the event data.
+    pub async fn get_received_events(
+        &self,
+        account_address: &AccountAddress,
+        start: u64,
+        limit: u16,
+    ) -> Result<Vec<AccountEvent>, AptosError> {
+        let event_handle = coin_store_type(&aptos_coin_type());
+
+        return self.get_account_events(account_address, &event_handle, DEPOSIT_EVENTS_FIELD, start, limit).await;
+    }
+
+    /// Returns the number of AptosCoin deposits of an account
+    pub async fn get_received_event_count(
+        &self,
+        account_address: &AccountAddress,
+    ) -> Result<u64, AptosError> {
+        let event_handle = coin_store_type(&aptos_coin_type());
+
+        return self.get_account_event_count(account_address, &event_handle, DEPOSIT_EVENTS_FIELD).await;
+    }
+
     /// Generate a transaction request that can be submitted to produce a raw transaction that can be signed
     /// which upon being signed can be submitted to the blockchain.
     /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/schemas/Transaction
     pub async fn generate_transaction(
         &self,
-        address: &str,
+        address: &AccountAddress,
         payload: serde_json::Value,
     ) -> Result<String, AptosError> {
+        return self.generate_transaction_with_gas(address, payload, DEFAULT_MAX_GAS_AMOUNT).await;
+    }
+
+    /// Same as `generate_transaction` with a gas limit, module publishing needs more than the default
+    pub async fn generate_transaction_with_gas(
+        &self,
+        address: &AccountAddress,
+        payload: serde_json::Value,
+        max_gas_amount: u64,
+    ) -> Result<String, AptosError> {
+        if let Err(error) = self.ensure_clock_synced().await {
+            return Err(error);
+        }
+
         let account: GetAccountResponse = match self.get_account(address).await {
             Ok(account) => account,
             Err(error) => return Err(error),
@@ -82,56 +332,58 @@ impl AptosRestClient {
             },
         };
 
-        let expiration_time: Duration = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
-            Ok(time) => time,
-            Err(error) => {
-                log::error!("{}", error);
-                return Err(AptosError::TimeWentBackwards)
-            },
-        };
+        return self.generate_transaction_at(address, payload, sequence_number, max_gas_amount, DEFAULT_GAS_UNIT_PRICE);
+    }
 
-        // Add 10 minutes (in seconds)
-        let expiration_time_secs: u64 = expiration_time.as_secs() + 600;
+    /// Same as `generate_transaction_with_gas` with a sequence number assigned by the caller,
+    /// to send several transactions from the same account without waiting for each of them.
+    /// A pending transaction is replaced by one with the same sequence number and a higher gas unit price.
+    pub fn generate_transaction_at(
+        &self,
+        address: &AccountAddress,
+        payload: serde_json::Value,
+        sequence_number: u64,
+        max_gas_amount: u64,
+        gas_unit_price: u64,
+    ) -> Result<String, AptosError> {
+        // Chain time, the device clock may be wrong
+        let expiration_time_secs: u64 = match self.clock.now_secs() {
+            Ok(now) => now + TRANSACTION_EXPIRATION_SECS,
+            Err(error) => return Err(error),
+        };
 
         return Ok(serde_json::json!({
-            "sender": format!("0x{}", address),
+            "sender": address.to_string(),
             "sequence_number": sequence_number.to_string(),
-            "max_gas_amount": "1000",
-            "gas_unit_price": "1",
-            "gas_currency_code": "XUS",
+            "max_gas_amount": max_gas_amount.to_string(),
+            "gas_unit_price": gas_unit_price.to_string(),
             "expiration_timestamp_secs": expiration_time_secs.to_string(),
             "payload": payload,
         }).to_string());
     }
     
-    /// Converts a transaction produced by `generate_transaction` into a properly signed transaction,
-    /// which can then be submitted to the blockchain.
-    /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/create_signing_message
+    /// Signs a transaction produced by `generate_transaction`, the signing message is computed locally
+    /// with the chain id of the node and the types of the arguments, returns the 0x prefixed signature
     pub async fn sign_transaction(
         &self,
-        account_from: &mut AptosAccount,
+        signer: &dyn Signer,
         transaction: String,
+        parameter_types: &[TypeTag],
     ) -> Result<String, AptosError> {
-        let response = match self.http_client
-            .post(format!("{}/transactions/signing_message", self.url))
-            .body(transaction)
-            .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .send()
-            .await {
-                Ok(res) => res,
-                Err(error) => {
-                    log::error!("{}", error);
-                    return Err(AptosError::InvalidRequest)
-                },
-            };
+        let chain_id = match self.chain_id().await {
+            Ok(chain_id) => chain_id,
+            Err(error) => return Err(error),
+        };
 
-        let signing_message = match handle_response::<CreateTransactionSigningResponse>(response).await {
-            Ok(message) => message,
+        let to_sign = match signing_message(&transaction, chain_id, parameter_types) {
+            Ok(signing_message) => signing_message,
             Err(error) => return Err(error),
         };
 
-        let to_sign = hex::decode(&signing_message.message[2..]).unwrap();
-        let signature: String = account_from.sign(&to_sign);
+        let signature: String = match signer.sign(&to_sign) {
+            Ok(signature) => signature,
+            Err(error) => return Err(error),
+        };
 
         return Ok(format!("0x{}", signature));
     }
@@ -142,12 +394,12 @@ impl AptosRestClient {
         transaction: String,
         signature_payload: serde_json::Value,
     ) -> Result<Transaction, AptosError> {
-        let mut transaction_json: serde_json::Value = serde_json::from_str(&transaction).unwrap();
+        let mut transaction_json = match node_transaction(&transaction) {
+            Ok(transaction_json) => transaction_json,
+            Err(error) => return Err(error),
+        };
 
-        transaction_json
-            .as_object_mut()
-            .unwrap()
-            .insert("signature".to_string(), signature_payload);
+        transaction_json["signature"] = signature_payload;
 
         let response = match self.http_client
             .post(format!("{}/transactions", self.url))
@@ -185,14 +437,66 @@ impl AptosRestClient {
         return handle_response::<Transaction>(response).await;
     }
 
+    /// Retrieve a committed transaction by its ledger version
+    pub async fn get_transaction_by_version(
+        &self,
+        version: u64
+    ) -> Result<Transaction, AptosError> {
+        return self.get_transaction(&version.to_string()).await;
+    }
+
+    /// Polls a transaction until it leaves the mempool, returns the committed transaction.
+    pub async fn wait_for_transaction(
+        &self,
+        transaction_hash: &str
+    ) -> Result<Transaction, AptosError> {
+        for _ in 0..TRANSACTION_WAIT_ATTEMPTS {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            let transaction = match self.get_transaction(transaction_hash).await {
+                Ok(transaction) => transaction,
+                // The node may not know the transaction yet
+                Err(AptosError::NotFound) => continue,
+                Err(error) => return Err(error),
+            };
+
+            if transaction.type_transaction != "pending_transaction" {
+                return Ok(transaction);
+            }
+        }
+
+        return Err(AptosError::TransactionTimeout);
+    }
+
     /// Returns the sequence number and authentication key for an account
     /// Specs here https://fullnode.devnet.aptoslabs.com/spec.html#/operations/get_account_transactions
     pub async fn get_account_transactions(
         &self, 
-        account_address: &str
+        account_address: &AccountAddress
     ) -> Result<Vec<Transaction>, AptosError> {
+        return self.get_account_transactions_page(account_address, None, None).await;
+    }
+
+    /// Returns the transactions sent by the account starting at a sequence number, in order
+    pub async fn get_account_transactions_page(
+        &self,
+        account_address: &AccountAddress,
+        start: Option<u64>,
+        limit: Option<u16>,
+    ) -> Result<Vec<Transaction>, AptosError> {
+        let mut query: Vec<(&str, String)> = Vec::new();
+
+        if let Some(start) = start {
+            query.push(("start", start.to_string()));
+        }
+
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+
         let response = match self.http_client
             .get(format!("{}/accounts/{}/transactions", self.url, account_address))
+            .query(&query)
             .header(reqwest::header::CONTENT_TYPE, "application/json")
             .send()
             .await {
@@ -213,6 +517,14 @@ impl AptosRestClient {
                 type_transaction: t["type"].as_str().unwrap().to_string(),
                 hash: t["hash"].as_str().unwrap().to_string(),
                 sequence_number: t["sequence_number"].as_str().unwrap().to_string(),
+                success: t["success"].as_bool(),
+                vm_status: t["vm_status"].as_str().map(|s| s.to_string()),
+                version: t["version"].as_str().map(|s| s.to_string()),
+                timestamp: t["timestamp"].as_str().map(|s| s.to_string()),
+                sender: t["sender"].as_str().map(|s| s.to_string()),
+                payload: t.get("payload").cloned(),
+                gas_used: t["gas_used"].as_str().map(|s| s.to_string()),
+                gas_unit_price: t["gas_unit_price"].as_str().map(|s| s.to_string()),
             }).collect(),
             None => return Err(AptosError::InvalidResponse),
         };
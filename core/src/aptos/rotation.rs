@@ -0,0 +1,80 @@
+use crate::aptos::*;
+use crate::aptos::bcs::BcsSerializer;
+
+/// Returns the message both the current and the new keys sign to authorize a rotation:
+/// bcs(SignedMessage<RotationProofChallenge>), the type info of `0x1::account::RotationProofChallenge` followed by the challenge
+/// Specs here https://github.com/aptos-labs/aptos-core/blob/main/aptos-move/framework/aptos-framework/sources/account.move
+pub fn rotation_proof_challenge(
+    sequence_number: u64,
+    originator: &AccountAddress,
+    current_authentication_key: &AccountAddress,
+    new_public_key_material: &[u8],
+) -> Vec<u8> {
+    let framework_address = AccountAddress::parse("0x1").unwrap();
+
+    let mut serializer = BcsSerializer::new();
+
+    serializer
+        .fixed_bytes(framework_address.as_bytes())
+        .str("account")
+        .str("RotationProofChallenge")
+        .u64(sequence_number)
+        .fixed_bytes(originator.as_bytes())
+        .fixed_bytes(current_authentication_key.as_bytes())
+        .bytes(new_public_key_material);
+
+    return serializer.finish();
+}
+
+/// Call of `0x1::account::rotate_authentication_key`,
+/// `cap_rotate_key` is the challenge signed by the current keys and `cap_update_table` by the new ones
+pub fn rotate_authentication_key_payload(
+    from: &AccountSigner,
+    to: &AccountSigner,
+    cap_rotate_key: &[u8],
+    cap_update_table: &[u8],
+) -> Result<EntryFunction, AptosError> {
+    let from_public_key_material = match from.public_key_material() {
+        Ok(material) => material,
+        Err(error) => return Err(error),
+    };
+
+    let to_public_key_material = match to.public_key_material() {
+        Ok(material) => material,
+        Err(error) => return Err(error),
+    };
+
+    let arguments = vec![
+        MoveValue::U8(from.scheme() as u8),
+        MoveValue::Bytes(from_public_key_material),
+        MoveValue::U8(to.scheme() as u8),
+        MoveValue::Bytes(to_public_key_material),
+        MoveValue::Bytes(cap_rotate_key.to_vec()),
+        MoveValue::Bytes(cap_update_table.to_vec()),
+    ];
+
+    return EntryFunction::new("0x1::account::rotate_authentication_key", Vec::new(), arguments);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_arguments_are_typed() {
+        let from = AccountSigner::single("aa");
+        let to = AccountSigner::single("bb");
+
+        let payload = rotate_authentication_key_payload(&from, &to, &[1, 2], &[3]).unwrap();
+
+        assert_eq!(payload.function_id(), "0x1::account::rotate_authentication_key");
+        assert_eq!(payload.arguments, vec![
+            MoveValue::U8(0),
+            MoveValue::Bytes(vec![0xaa]),
+            MoveValue::U8(0),
+            MoveValue::Bytes(vec![0xbb]),
+            MoveValue::Bytes(vec![1, 2]),
+            MoveValue::Bytes(vec![3]),
+        ]);
+    }
+}
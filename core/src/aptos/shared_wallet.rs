@@ -1,8 +1,14 @@
 use ed25519_dalek::{PublicKey};
-use tiny_keccak::{Sha3, Hasher};
+
+use crate::aptos::*;
+
+/// Size of the signature bitmap of multi ed25519 authenticators, in bits
+pub const MAX_PUBLIC_KEYS: usize = 32;
 
 pub struct AptosSharedWallet {
     pub public_keys: Vec<PublicKey>,
+    /// Number of signatures required, all the keys when not set
+    threshold: Option<u8>,
 }
 
 #[allow(dead_code)]
@@ -10,34 +16,127 @@ impl AptosSharedWallet {
     pub fn new() -> Self {
         return Self {
             public_keys: Vec::new(),
+            threshold: None,
         }
     }
 
-    pub fn add_public_key(&mut self, public_key: String) {
-        let public_key_bytes = hex::decode(public_key).unwrap();
+    pub fn add_public_key(&mut self, public_key: String) -> Result<(), AptosError> {
+        if self.public_keys.len() >= MAX_PUBLIC_KEYS {
+            log::error!("A shared wallet has at most {} public keys", MAX_PUBLIC_KEYS);
+            return Err(AptosError::TooManyPublicKeys);
+        }
+
+        let public_key_bytes = match hex::decode(public_key.trim_start_matches("0x")) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::error!("Invalid public key {}: {}", public_key, error);
+                return Err(AptosError::InvalidKeypair)
+            },
+        };
+
+        return match PublicKey::from_bytes(&public_key_bytes) {
+            Ok(key) => {
+                self.public_keys.push(key);
+                Ok(())
+            },
+            Err(error) => {
+                log::error!("Invalid public key {}: {}", public_key, error);
+                Err(AptosError::InvalidKeypair)
+            },
+        };
+    }
+
+    /// Sets the number of signatures required, between one and the number of keys added
+    pub fn set_threshold(&mut self, threshold: u32) -> Result<(), AptosError> {
+        if threshold == 0 || threshold as usize > self.public_keys.len() {
+            log::error!("Threshold {} out of 1..={}", threshold, self.public_keys.len());
+            return Err(AptosError::InvalidThreshold);
+        }
+
+        self.threshold = Some(threshold as u8);
 
-        self.public_keys.push(
-            PublicKey::from_bytes(&public_key_bytes).unwrap()
-        );
+        return Ok(());
+    }
+
+    /// Returns the number of signatures required to submit a transaction
+    pub fn threshold(&self) -> u8 {
+        return self.threshold.unwrap_or(self.public_keys.len() as u8);
+    }
+
+    /// Returns the authentication key of the wallet: the public keys followed by the threshold
+    pub fn authentication_key(&self) -> AccountAddress {
+        let public_key_material = public_key_material(&self.public_keys, Some(self.threshold()));
+
+        return authentication_key(&public_key_material, AuthenticationScheme::MultiEd25519);
     }
 
     /// Returns the address associated with the given wallet
-    pub fn address(&self) -> String {
-        let mut sha3 = Sha3::v256();
+    pub fn address(&self) -> AccountAddress {
+        return self.authentication_key();
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SecretKey;
+
+    fn public_key(seed: u8) -> String {
+        let secret_key = SecretKey::from_bytes(&[seed; 32]).unwrap();
 
-        for public_key in &self.public_keys {
-            sha3.update(public_key.as_bytes());
+        return hex::encode(PublicKey::from(&secret_key).as_bytes());
+    }
+
+    fn wallet(keys_count: u8) -> AptosSharedWallet {
+        let mut wallet = AptosSharedWallet::new();
+
+        for seed in 0..keys_count {
+            wallet.add_public_key(public_key(seed)).unwrap();
         }
 
-        let k: usize = self.public_keys.len();
+        return wallet;
+    }
+
+    #[test]
+    fn threshold_defaults_to_all_keys() {
+        assert_eq!(wallet(3).threshold(), 3);
+    }
+
+    #[test]
+    fn threshold_must_be_between_one_and_the_number_of_keys() {
+        let mut wallet = wallet(3);
+
+        assert!(matches!(wallet.set_threshold(0), Err(AptosError::InvalidThreshold)));
+        assert!(matches!(wallet.set_threshold(4), Err(AptosError::InvalidThreshold)));
+        assert!(matches!(wallet.set_threshold(256 + 2), Err(AptosError::InvalidThreshold)));
 
-        sha3.update(&vec![k as u8]);
+        wallet.set_threshold(2).unwrap();
+        assert_eq!(wallet.threshold(), 2);
+    }
+
+    #[test]
+    fn account_signer_has_the_wallet_authentication_key() {
+        let mut wallet = wallet(3);
+        wallet.set_threshold(2).unwrap();
+
+        let account_signer = AccountSigner::multi(&wallet);
+
+        assert_eq!(account_signer.authentication_key().unwrap(), wallet.authentication_key());
+        assert_ne!(AccountSigner::multi(&self::wallet(3)).authentication_key().unwrap(), wallet.authentication_key());
+    }
+
+    #[test]
+    fn rejects_invalid_public_keys() {
+        let mut wallet = AptosSharedWallet::new();
 
-        sha3.update(&vec![1u8]);
+        assert!(matches!(wallet.add_public_key("0xnothex".to_string()), Err(AptosError::InvalidKeypair)));
+        assert!(matches!(wallet.add_public_key("0x0102".to_string()), Err(AptosError::InvalidKeypair)));
+        assert!(wallet.public_keys.is_empty());
+    }
 
-        let mut output = [0u8; 32];
-        sha3.finalize(&mut output);
+    #[test]
+    fn rejects_more_keys_than_the_bitmap_holds() {
+        let mut wallet = wallet(MAX_PUBLIC_KEYS as u8);
 
-        return hex::encode(output);
+        assert!(matches!(wallet.add_public_key(public_key(MAX_PUBLIC_KEYS as u8)), Err(AptosError::TooManyPublicKeys)));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::ffi::{c_void, CString};
+use std::path::{Path, PathBuf};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+
+pub const SIGNATURE_LENGTH: usize = 64;
+
+/// Produces ed25519 signatures, the private key may live outside of the core
+pub trait Signer: Send + Sync {
+    /// Returns the public key hex encoded
+    fn public_key(&self) -> String;
+
+    /// Returns the signature of the message hex encoded
+    fn sign(&self, message: &[u8]) -> Result<String, AptosError>;
+}
+
+// In-memory keys
+
+impl Signer for AptosAccount {
+    fn public_key(&self) -> String {
+        return AptosAccount::public_key(self);
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<String, AptosError> {
+        return Ok(AptosAccount::sign(self, message));
+    }
+}
+
+// Keystore keys
+
+/// Parameters of scrypt deriving the keystore key from its password, lighter in tests
+#[cfg(not(test))]
+const SCRYPT_LOG_N: u8 = 15;
+#[cfg(test)]
+const SCRYPT_LOG_N: u8 = 4;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+const KEYSTORE_VERSION: u32 = 1;
+
+/// Keypair encrypted with XChaCha20-Poly1305, its public key is the associated data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedKeypair {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    salt: String,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    /// Hex encoded public keys to their encrypted keypair
+    keypairs: HashMap<String, EncryptedKeypair>,
+}
+
+/// JSON file of keypairs encrypted with a key derived from a password with scrypt.
+/// The password is given by the host, which keeps it in the platform keychain.
+/// Files of plain keypairs written by earlier versions are encrypted when opened.
+pub struct Keystore {
+    path: PathBuf,
+    file: KeystoreFile,
+    key: [u8; 32],
+}
+
+impl Keystore {
+    /// Opens the keystore, an empty one is created if the file does not exist yet.
+    /// Fails with `InvalidPassword` if the password doesn't decrypt the keys already stored.
+    pub fn open(path: &Path, password: &str) -> Result<Self, AptosError> {
+        if password.is_empty() {
+            log::error!("The keystore needs a password");
+            return Err(AptosError::InvalidPassword);
+        }
+
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => Some(content),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidKeystore)
+            },
+        };
+
+        let parsed: Option<serde_json::Value> = match content.as_ref().map(|content| serde_json::from_str(content)) {
+            Some(Ok(value)) => Some(value),
+            Some(Err(error)) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidKeystore)
+            },
+            None => None,
+        };
+
+        let (file, plain_keypairs) = match parsed {
+            Some(value) if value.get("version").is_some() => match serde_json::from_value::<KeystoreFile>(value) {
+                Ok(file) => (file, HashMap::new()),
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidKeystore)
+                },
+            },
+            // Plain keypairs of the first keystore format
+            Some(value) => match serde_json::from_value::<HashMap<String, String>>(value) {
+                Ok(plain_keypairs) => (new_keystore_file(), plain_keypairs),
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::InvalidKeystore)
+                },
+            },
+            None => (new_keystore_file(), HashMap::new()),
+        };
+
+        let key = match derive_key(password, &file) {
+            Ok(key) => key,
+            Err(error) => return Err(error),
+        };
+
+        let mut keystore = Self {
+            path: path.to_path_buf(),
+            file: file,
+            key: key,
+        };
+
+        // Every key is encrypted with the same password, checking one of them is enough
+        if let Some(public_key) = keystore.file.keypairs.keys().next().cloned() {
+            if let Err(error) = keystore.account(&public_key) {
+                return Err(error);
+            }
+        }
+
+        if !plain_keypairs.is_empty() {
+            for keypair in plain_keypairs.values() {
+                match AptosAccount::from_keypair(keypair) {
+                    Ok(account) => keystore.encrypt(&account),
+                    Err(error) => return Err(error),
+                }
+            }
+
+            if let Err(error) = keystore.write() {
+                return Err(error);
+            }
+        }
+
+        return Ok(keystore);
+    }
+
+    /// Adds the account keypair and writes the keystore
+    pub fn insert(&mut self, account: &AptosAccount) -> Result<(), AptosError> {
+        self.encrypt(account);
+
+        return self.write();
+    }
+
+    pub fn account(&self, public_key: &str) -> Result<AptosAccount, AptosError> {
+        let public_key = public_key.trim_start_matches("0x").to_lowercase();
+
+        let encrypted_keypair = match self.file.keypairs.get(&public_key) {
+            Some(encrypted_keypair) => encrypted_keypair,
+            None => {
+                log::error!("No key for {} in the keystore", public_key);
+                return Err(AptosError::InvalidKeypair)
+            },
+        };
+
+        let (nonce, ciphertext) = match (hex::decode(&encrypted_keypair.nonce), hex::decode(&encrypted_keypair.ciphertext)) {
+            (Ok(nonce), Ok(ciphertext)) if nonce.len() == 24 => (nonce, ciphertext),
+            _ => {
+                log::error!("Malformed keystore entry for {}", public_key);
+                return Err(AptosError::InvalidKeystore)
+            },
+        };
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key));
+
+        let keypair = match cipher.decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: public_key.as_bytes() }) {
+            Ok(keypair) => keypair,
+            Err(_) => {
+                log::error!("The keystore password doesn't decrypt the key of {}", public_key);
+                return Err(AptosError::InvalidPassword)
+            },
+        };
+
+        let account = match AptosAccount::from_keypair(&hex::encode(keypair)) {
+            Ok(account) => account,
+            Err(error) => return Err(error),
+        };
+
+        if account.public_key() != public_key {
+            log::error!("The keystore entry of {} holds another key", public_key);
+            return Err(AptosError::InvalidKeystore);
+        }
+
+        return Ok(account);
+    }
+
+    fn encrypt(&mut self, account: &AptosAccount) {
+        let public_key = account.public_key();
+
+        let mut nonce = [0u8; 24];
+        OsRng.fill_bytes(&mut nonce);
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key));
+
+        // Encryption only fails for messages larger than the cipher allows
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), Payload { msg: &account.keypair.to_bytes(), aad: public_key.as_bytes() })
+            .unwrap();
+
+        self.file.keypairs.insert(public_key, EncryptedKeypair {
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        });
+    }
+
+    fn write(&self) -> Result<(), AptosError> {
+        let content = serde_json::to_string(&self.file).unwrap();
+
+        return match std::fs::write(&self.path, content) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::InvalidKeystore)
+            },
+        };
+    }
+}
+
+fn new_keystore_file() -> KeystoreFile {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+
+    return KeystoreFile {
+        version: KEYSTORE_VERSION,
+        salt: hex::encode(salt),
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        keypairs: HashMap::new(),
+    };
+}
+
+fn derive_key(password: &str, file: &KeystoreFile) -> Result<[u8; 32], AptosError> {
+    let (salt, params) = match (hex::decode(&file.salt), scrypt::Params::new(file.log_n, file.r, file.p)) {
+        (Ok(salt), Ok(params)) => (salt, params),
+        _ => {
+            log::error!("Invalid keystore key derivation parameters");
+            return Err(AptosError::InvalidKeystore)
+        },
+    };
+
+    let mut key = [0u8; 32];
+
+    return match scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key) {
+        Ok(()) => Ok(key),
+        Err(error) => {
+            log::error!("{}", error);
+            Err(AptosError::InvalidKeystore)
+        },
+    };
+}
+
+/// Signs with a key of the keystore, the key is only loaded while signing
+pub struct KeystoreSigner {
+    path: PathBuf,
+    password: String,
+    public_key: String,
+}
+
+impl KeystoreSigner {
+    pub fn new(path: &Path, password: &str, public_key: &str) -> Self {
+        return Self {
+            path: path.to_path_buf(),
+            password: password.to_string(),
+            public_key: public_key.trim_start_matches("0x").to_string(),
+        };
+    }
+}
+
+impl Signer for KeystoreSigner {
+    fn public_key(&self) -> String {
+        return self.public_key.clone();
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<String, AptosError> {
+        // Reading the file and deriving the key take a while, other tasks move to another worker
+        let account = match blocking(|| Keystore::open(&self.path, &self.password).and_then(|keystore| keystore.account(&self.public_key))) {
+            Ok(account) => account,
+            Err(error) => return Err(error),
+        };
+
+        return Ok(account.sign(message));
+    }
+}
+
+// Host keys
+
+/// Callback registered by the host to sign with keys it owns (Secure Enclave, external device...).
+/// `sign` writes the 64 bytes signature of the message for the hex encoded public key and returns true on success.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RustSignerCallback {
+    pub swift_signer_ptr: *const c_void,
+    pub sign: extern "C" fn(*const c_void, *const std::os::raw::c_char, *const u8, usize, *mut u8) -> bool,
+}
+unsafe impl Send for RustSignerCallback {}
+// The host side must be thread safe
+unsafe impl Sync for RustSignerCallback {}
+
+/// Signs by calling back into the host
+pub struct FfiSigner {
+    callback: RustSignerCallback,
+    public_key: String,
+}
+
+impl FfiSigner {
+    pub fn new(callback: RustSignerCallback, public_key: &str) -> Self {
+        return Self {
+            callback: callback,
+            public_key: public_key.trim_start_matches("0x").to_string(),
+        };
+    }
+}
+
+impl Signer for FfiSigner {
+    fn public_key(&self) -> String {
+        return self.public_key.clone();
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<String, AptosError> {
+        let public_key = match hex::decode(&self.public_key).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => {
+                log::error!("Invalid public key {}", self.public_key);
+                return Err(AptosError::InvalidKeypair)
+            },
+        };
+
+        let public_key_string = match CString::new(self.public_key.clone()) {
+            Ok(public_key_string) => public_key_string,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidKeypair)
+            },
+        };
+
+        let mut signature = [0u8; SIGNATURE_LENGTH];
+
+        // The host may prompt the user, other tasks move to another worker meanwhile
+        let signed = blocking(|| (self.callback.sign)(
+            self.callback.swift_signer_ptr,
+            public_key_string.as_ptr(),
+            message.as_ptr(),
+            message.len(),
+            signature.as_mut_ptr(),
+        ));
+
+        if !signed {
+            log::error!("The host failed to sign with {}", self.public_key);
+            return Err(AptosError::SigningFailed);
+        }
+
+        // A signature of another key or message would only be rejected by the node
+        let is_valid = Signature::from_bytes(&signature)
+            .map(|signature| public_key.verify(message, &signature).is_ok())
+            .unwrap_or(false);
+
+        if !is_valid {
+            log::error!("The host returned an invalid signature for {}", self.public_key);
+            return Err(AptosError::InvalidSignature);
+        }
+
+        return Ok(hex::encode(signature));
+    }
+}
+
+/// Runs a blocking call, on a runtime worker the other tasks are moved to another worker first
+fn blocking<T, F: FnOnce() -> T>(f: F) -> T {
+    return match tokio::runtime::Handle::try_current() {
+        Ok(_) => tokio::task::block_in_place(f),
+        Err(_) => f(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("keystore-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        return path;
+    }
+
+    #[test]
+    fn keystore_keys_are_encrypted() {
+        let path = keystore_path("encrypted");
+        let account = AptosAccount::new(None);
+
+        Keystore::open(&path, "password").unwrap().insert(&account).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains(&account.keypair()));
+        assert!(!content.contains(&hex::encode(account.keypair.secret.as_bytes())));
+
+        let stored = Keystore::open(&path, "password").unwrap().account(&account.public_key()).unwrap();
+        assert_eq!(stored.keypair(), account.keypair());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keystore_rejects_another_password() {
+        let path = keystore_path("password");
+
+        Keystore::open(&path, "password").unwrap().insert(&AptosAccount::new(None)).unwrap();
+
+        assert!(matches!(Keystore::open(&path, "other"), Err(AptosError::InvalidPassword)));
+        assert!(matches!(Keystore::open(&path, ""), Err(AptosError::InvalidPassword)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keystore_encrypts_plain_keypairs_when_opened() {
+        let path = keystore_path("plain");
+        let account = AptosAccount::new(None);
+
+        let mut plain_keypairs: HashMap<String, String> = HashMap::new();
+        plain_keypairs.insert(account.public_key(), account.keypair());
+        std::fs::write(&path, serde_json::to_string(&plain_keypairs).unwrap()).unwrap();
+
+        let keystore = Keystore::open(&path, "password").unwrap();
+        assert_eq!(keystore.account(&account.public_key()).unwrap().keypair(), account.keypair());
+        assert!(!std::fs::read_to_string(&path).unwrap().contains(&account.keypair()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    extern "C" fn sign_with_zeros(_: *const c_void, _: *const std::os::raw::c_char, _: *const u8, _: usize, signature: *mut u8) -> bool {
+        unsafe { std::ptr::write_bytes(signature, 0, SIGNATURE_LENGTH) };
+
+        return true;
+    }
+
+    #[test]
+    fn ffi_signer_rejects_signatures_of_another_key() {
+        let callback = RustSignerCallback {
+            swift_signer_ptr: std::ptr::null(),
+            sign: sign_with_zeros,
+        };
+
+        let signer = FfiSigner::new(callback, &AptosAccount::new(None).public_key());
+
+        assert!(matches!(signer.sign(b"message"), Err(AptosError::InvalidSignature)));
+    }
+}
@@ -0,0 +1,385 @@
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+
+const BUNDLE_FORMAT: &str = "aptos-partially-signed-transaction";
+/// Version 2 adds the chain id, the signatures of older bundles can't be checked offline.
+/// Version 3 adds the types of the arguments, the signed arguments are encoded from them.
+const BUNDLE_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// Partially signed transaction of a shared wallet passed between the cosigners.
+/// It holds the raw transaction, the wallet definition and the signatures collected so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionBundle {
+    pub format: String,
+    pub version: u32,
+    /// Raw transaction produced by `generate_transaction`
+    pub transaction: String,
+    /// Public keys of the wallet in order, hex encoded without prefix
+    pub public_keys: Vec<String>,
+    pub threshold: u8,
+    pub signatures: Vec<BundleSignature>,
+    /// Chain the transaction is signed for, part of the signing message
+    #[serde(default)]
+    pub chain_id: u8,
+    /// Types of the arguments of the called function taken from its ABI, checked again before submitting
+    #[serde(default)]
+    pub parameter_types: Vec<String>,
+}
+
+impl TransactionBundle {
+    pub fn new(transaction: String, wallet: &AptosSharedWallet, chain_id: u8, parameter_types: &[TypeTag]) -> Result<Self, AptosError> {
+        let bundle = Self {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            transaction: transaction,
+            public_keys: wallet.public_keys.iter().map(|key| hex::encode(key.as_bytes())).collect(),
+            threshold: wallet.threshold(),
+            signatures: Vec::new(),
+            chain_id: chain_id,
+            parameter_types: parameter_types.iter().map(|parameter_type| parameter_type.to_string()).collect(),
+        };
+
+        // The transaction must be one the cosigners can sign
+        if let Err(error) = bundle.signing_message() {
+            return Err(error);
+        }
+
+        return Ok(bundle);
+    }
+
+    /// Returns the shared wallet the transaction is sent from
+    pub fn wallet(&self) -> Result<AptosSharedWallet, AptosError> {
+        let mut wallet = AptosSharedWallet::new();
+
+        for public_key in &self.public_keys {
+            if let Err(error) = wallet.add_public_key(public_key.clone()) {
+                return Err(error);
+            }
+        }
+
+        if let Err(error) = wallet.set_threshold(self.threshold as u32) {
+            return Err(error);
+        }
+
+        return Ok(wallet);
+    }
+
+    pub fn parameter_types(&self) -> Result<Vec<TypeTag>, AptosError> {
+        return self.parameter_types.iter().map(|parameter_type| TypeTag::parse(parameter_type)).collect();
+    }
+
+    /// Returns the message the cosigners sign, which needs the chain id of version 2 bundles
+    pub fn signing_message(&self) -> Result<Vec<u8>, AptosError> {
+        if self.chain_id == 0 {
+            log::error!("The bundle has no chain id");
+            return Err(AptosError::InvalidChainId);
+        }
+
+        let parameter_types = match self.parameter_types() {
+            Ok(parameter_types) => parameter_types,
+            Err(error) => return Err(error),
+        };
+
+        return signing_message(&self.transaction, self.chain_id, &parameter_types);
+    }
+
+    /// Adds the signature of a cosigner, replacing the previous one of the same key.
+    /// Signatures that don't match the transaction are rejected when the bundle has a chain id.
+    pub fn add_signature(&mut self, public_key: &str, signature: &str) -> Result<(), AptosError> {
+        let public_key = public_key.trim_start_matches("0x").to_lowercase();
+        let signature = signature.trim_start_matches("0x").to_lowercase();
+
+        if !self.public_keys.contains(&public_key) {
+            log::error!("{} is not a public key of the wallet", public_key);
+            return Err(AptosError::UnknownSigner);
+        }
+
+        let status = match self.signing_message() {
+            Ok(signing_message) => verify_signature(&public_key, &signing_message, &signature),
+            Err(_) => match hex::decode(&signature) {
+                Ok(bytes) if bytes.len() == SIGNATURE_LENGTH => SignatureStatus::Valid,
+                _ => SignatureStatus::Malformed,
+            },
+        };
+
+        if status != SignatureStatus::Valid {
+            log::error!("Signature of {} rejected: {:?}", public_key, status);
+            return Err(AptosError::InvalidSignature);
+        }
+
+        self.signatures.retain(|s| s.public_key != public_key);
+
+        self.signatures.push(BundleSignature {
+            public_key: public_key,
+            signature: signature,
+        });
+
+        return Ok(());
+    }
+
+    /// Adds the signatures of another bundle of the same transaction and wallet
+    pub fn merge(&mut self, other: &TransactionBundle) -> Result<(), AptosError> {
+        if self.transaction != other.transaction || self.public_keys != other.public_keys || self.threshold != other.threshold || self.chain_id != other.chain_id || self.parameter_types != other.parameter_types {
+            log::error!("Bundles of different transactions can't be merged");
+            return Err(AptosError::BundleMismatch);
+        }
+
+        for signature in &other.signatures {
+            if !self.signatures.iter().any(|s| s.public_key == signature.public_key) {
+                self.signatures.push(signature.clone());
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns true when enough valid signatures are collected to submit the transaction,
+    /// always false for bundles without chain id whose signatures can't be checked
+    pub fn is_complete(&self) -> bool {
+        let signing_message = match self.signing_message() {
+            Ok(signing_message) => signing_message,
+            Err(_) => return false,
+        };
+
+        return self.verified_authenticator(&signing_message).is_ok();
+    }
+
+    pub fn signed_payloads(&self) -> Vec<SignedPayload> {
+        return self.signatures.iter().map(|s| SignedPayload {
+            public_key: s.public_key.clone(),
+            signature: s.signature.clone(),
+        }).collect();
+    }
+
+    /// Returns the signature payload to submit with the transaction, only the signatures valid for the signing message are kept
+    pub fn verified_authenticator(&self, signing_message: &[u8]) -> Result<serde_json::Value, AptosError> {
+        return match self.wallet() {
+            Ok(wallet) => AccountSigner::multi(&wallet).verified_authenticator(signing_message, &self.signed_payloads()),
+            Err(error) => Err(error),
+        };
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        return serde_json::to_vec(self).unwrap();
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AptosError> {
+        let bundle: TransactionBundle = match serde_json::from_slice(bytes) {
+            Ok(bundle) => bundle,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::InvalidBundle)
+            },
+        };
+
+        if bundle.format != BUNDLE_FORMAT || bundle.version > BUNDLE_VERSION {
+            log::error!("Unsupported bundle {} version {}", bundle.format, bundle.version);
+            return Err(AptosError::InvalidBundle);
+        }
+
+        if let Err(error) = bundle.validate() {
+            log::error!("Invalid bundle: {:?}", error);
+            return Err(AptosError::InvalidBundle);
+        }
+
+        return Ok(bundle);
+    }
+
+    /// Checks what comes from other devices: the wallet keys and threshold, the transaction and who signed.
+    /// The arguments shown are the ones signed, the encoding kept with the payload must be the one of its JSON arguments.
+    fn validate(&self) -> Result<(), AptosError> {
+        if let Err(error) = self.wallet() {
+            return Err(error);
+        }
+
+        let parameter_types = match self.parameter_types() {
+            Ok(parameter_types) => parameter_types,
+            Err(error) => return Err(error),
+        };
+
+        if let Err(error) = raw_transaction_bcs(&self.transaction, self.chain_id, &parameter_types) {
+            return Err(error);
+        }
+
+        for signature in &self.signatures {
+            if !self.public_keys.contains(&signature.public_key) {
+                log::error!("{} is not a public key of the wallet", signature.public_key);
+                return Err(AptosError::UnknownSigner);
+            }
+
+            match hex::decode(&signature.signature) {
+                Ok(bytes) if bytes.len() == SIGNATURE_LENGTH => (),
+                _ => {
+                    log::error!("Malformed signature of {}", signature.public_key);
+                    return Err(AptosError::InvalidSignature)
+                },
+            };
+        }
+
+        return Ok(());
+    }
+
+    pub fn to_base64(&self) -> String {
+        return base64::encode(self.to_bytes());
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, AptosError> {
+        return match base64::decode(encoded.trim()) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::InvalidBundle)
+            },
+        };
+    }
+
+    pub fn write_file(&self, path: &Path) -> Result<(), AptosError> {
+        return match std::fs::write(path, self.to_bytes()) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::InvalidBundle)
+            },
+        };
+    }
+
+    pub fn read_file(path: &Path) -> Result<Self, AptosError> {
+        return match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(error) => {
+                log::error!("{}", error);
+                Err(AptosError::InvalidBundle)
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION: &str = r#"{"sender":"0x1","sequence_number":"0","max_gas_amount":"1000","gas_unit_price":"1","expiration_timestamp_secs":"1700000000","payload":{"type":"entry_function_payload","function":"0x1::coin::transfer","type_arguments":[],"arguments":["0x2","100"],"arguments_bcs":["0x0000000000000000000000000000000000000000000000000000000000000002","0x6400000000000000"]}}"#;
+    const CHAIN_ID: u8 = 4;
+
+    fn cosigners() -> Vec<AptosAccount> {
+        return (0..3).map(|_| AptosAccount::new(None)).collect();
+    }
+
+    fn bundle(cosigners: &[AptosAccount], threshold: u32) -> TransactionBundle {
+        let mut wallet = AptosSharedWallet::new();
+
+        for cosigner in cosigners {
+            wallet.add_public_key(cosigner.public_key()).unwrap();
+        }
+
+        wallet.set_threshold(threshold).unwrap();
+
+        return TransactionBundle::new(TRANSACTION.to_string(), &wallet, CHAIN_ID, &[TypeTag::Address, TypeTag::U64]).unwrap();
+    }
+
+    fn sign(bundle: &mut TransactionBundle, cosigner: &AptosAccount) {
+        let signature = cosigner.sign(&bundle.signing_message().unwrap());
+
+        bundle.add_signature(&cosigner.public_key(), &signature).unwrap();
+    }
+
+    #[test]
+    fn complete_once_the_threshold_of_valid_signatures_is_met() {
+        let cosigners = cosigners();
+        let mut bundle = bundle(&cosigners, 2);
+
+        sign(&mut bundle, &cosigners[0]);
+        assert!(!bundle.is_complete());
+
+        sign(&mut bundle, &cosigners[2]);
+        assert!(bundle.is_complete());
+    }
+
+    #[test]
+    fn rejects_signatures_of_another_message() {
+        let cosigners = cosigners();
+        let mut bundle = bundle(&cosigners, 1);
+
+        let signature = cosigners[0].sign(b"another message");
+
+        assert!(matches!(bundle.add_signature(&cosigners[0].public_key(), &signature), Err(AptosError::InvalidSignature)));
+        assert!(!bundle.is_complete());
+    }
+
+    #[test]
+    fn forged_signatures_are_not_counted() {
+        let cosigners = cosigners();
+        let mut bundle = bundle(&cosigners, 1);
+
+        bundle.signatures.push(BundleSignature {
+            public_key: cosigners[1].public_key(),
+            signature: "00".repeat(SIGNATURE_LENGTH),
+        });
+
+        let bundle = TransactionBundle::from_bytes(&bundle.to_bytes()).unwrap();
+        assert!(!bundle.is_complete());
+    }
+
+    #[test]
+    fn rejects_malformed_bundles() {
+        let cosigners = cosigners();
+        let bundle = bundle(&cosigners, 2);
+
+        let mut invalid_key = bundle.clone();
+        invalid_key.public_keys[1] = "not hex".to_string();
+        assert!(matches!(TransactionBundle::from_bytes(&invalid_key.to_bytes()), Err(AptosError::InvalidBundle)));
+
+        let mut invalid_threshold = bundle.clone();
+        invalid_threshold.threshold = 4;
+        assert!(matches!(TransactionBundle::from_bytes(&invalid_threshold.to_bytes()), Err(AptosError::InvalidBundle)));
+
+        let mut unknown_signer = bundle.clone();
+        unknown_signer.signatures.push(BundleSignature {
+            public_key: AptosAccount::new(None).public_key(),
+            signature: "00".repeat(SIGNATURE_LENGTH),
+        });
+        assert!(matches!(TransactionBundle::from_bytes(&unknown_signer.to_bytes()), Err(AptosError::InvalidBundle)));
+
+        assert!(matches!(TransactionBundle::from_bytes(b"{}"), Err(AptosError::InvalidBundle)));
+    }
+
+    #[test]
+    fn rejects_arguments_that_are_not_the_ones_signed() {
+        let cosigners = cosigners();
+        let bundle = bundle(&cosigners, 2);
+
+        let with_payload = |update: &dyn Fn(&mut serde_json::Value)| {
+            let mut transaction: serde_json::Value = serde_json::from_str(&bundle.transaction).unwrap();
+            update(&mut transaction["payload"]);
+
+            let mut tampered = bundle.clone();
+            tampered.transaction = transaction.to_string();
+
+            return TransactionBundle::from_bytes(&tampered.to_bytes());
+        };
+
+        // The amount shown is not the one encoded
+        assert!(matches!(with_payload(&|payload| payload["arguments"][1] = serde_json::json!("1000")), Err(AptosError::InvalidBundle)));
+        assert!(matches!(with_payload(&|payload| payload[ARGUMENTS_BCS_FIELD][1] = serde_json::json!("0xe803000000000000")), Err(AptosError::InvalidBundle)));
+
+        // Argument types that don't fit the arguments
+        let mut tampered = bundle.clone();
+        tampered.parameter_types[1] = "u128".to_string();
+        assert!(matches!(TransactionBundle::from_bytes(&tampered.to_bytes()), Err(AptosError::InvalidBundle)));
+
+        let mut tampered = bundle.clone();
+        tampered.parameter_types.pop();
+        assert!(matches!(TransactionBundle::from_bytes(&tampered.to_bytes()), Err(AptosError::InvalidBundle)));
+
+        // Without their encoding the arguments are signed from their JSON form
+        assert!(with_payload(&|payload| { payload.as_object_mut().unwrap().remove(ARGUMENTS_BCS_FIELD); }).is_ok());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::aptos::*;
+use crate::aptos::bcs::BcsSerializer;
+
+const TRANSACTION_SALT: &[u8] = b"APTOS::Transaction";
+const USER_TRANSACTION_VARIANT: u64 = 0;
+const ED25519_AUTHENTICATOR_VARIANT: u64 = 0;
+const MULTI_ED25519_AUTHENTICATOR_VARIANT: u64 = 1;
+
+/// Returns the hash the transaction will have once committed, known before it is submitted,
+/// computed locally as sha3_256(sha3_256("APTOS::Transaction") || 0 || bcs(signed_transaction))
+/// where the signed transaction is the raw transaction followed by its authenticator
+pub fn transaction_hash(transaction: &str, signature_payload: &serde_json::Value, chain_id: u8, parameter_types: &[TypeTag]) -> Result<String, AptosError> {
+    let raw_transaction = match raw_transaction_bcs(transaction, chain_id, parameter_types) {
+        Ok(bytes) => bytes,
+        Err(error) => return Err(error),
+    };
+
+    let authenticator = match authenticator_bcs(signature_payload) {
+        Ok(bytes) => bytes,
+        Err(error) => return Err(error),
+    };
+
+    let mut serializer = BcsSerializer::new();
+
+    serializer
+        .uleb128(USER_TRANSACTION_VARIANT)
+        .fixed_bytes(&raw_transaction)
+        .fixed_bytes(&authenticator);
+
+    let mut message = sha3_256(TRANSACTION_SALT).to_vec();
+    message.extend_from_slice(&serializer.finish());
+
+    return Ok(format!("0x{}", hex::encode(sha3_256(&message))));
+}
+
+/// BCS encoding of a signature payload produced by `AccountSigner::authenticator`
+pub fn authenticator_bcs(signature_payload: &serde_json::Value) -> Result<Vec<u8>, AptosError> {
+    let mut serializer = BcsSerializer::new();
+
+    match signature_payload["type"].as_str() {
+        Some("ed25519_signature") => {
+            let public_key = match json_hex(&signature_payload["public_key"]) {
+                Ok(bytes) => bytes,
+                Err(error) => return Err(error),
+            };
+
+            let signature = match json_hex(&signature_payload["signature"]) {
+                Ok(bytes) => bytes,
+                Err(error) => return Err(error),
+            };
+
+            serializer
+                .uleb128(ED25519_AUTHENTICATOR_VARIANT)
+                .bytes(&public_key)
+                .bytes(&signature);
+        },
+        Some("multi_ed25519_signature") => {
+            // The keys followed by the threshold, the signatures followed by the bitmap
+            let mut public_keys: Vec<u8> = Vec::new();
+            let mut signatures: Vec<u8> = Vec::new();
+
+            for public_key in signature_payload["public_keys"].as_array().cloned().unwrap_or_default() {
+                match json_hex(&public_key) {
+                    Ok(bytes) => public_keys.extend_from_slice(&bytes),
+                    Err(error) => return Err(error),
+                }
+            }
+
+            for signature in signature_payload["signatures"].as_array().cloned().unwrap_or_default() {
+                match json_hex(&signature) {
+                    Ok(bytes) => signatures.extend_from_slice(&bytes),
+                    Err(error) => return Err(error),
+                }
+            }
+
+            match signature_payload["threshold"].as_u64() {
+                Some(threshold) => public_keys.push(threshold as u8),
+                None => {
+                    log::error!("Invalid threshold {}", signature_payload["threshold"]);
+                    return Err(AptosError::InvalidSignature)
+                },
+            };
+
+            match json_hex(&signature_payload["bitmap"]) {
+                Ok(bitmap) => signatures.extend_from_slice(&bitmap),
+                Err(error) => return Err(error),
+            };
+
+            serializer
+                .uleb128(MULTI_ED25519_AUTHENTICATOR_VARIANT)
+                .bytes(&public_keys)
+                .bytes(&signatures);
+        },
+        _ => {
+            log::error!("Unsupported signature {}", signature_payload["type"]);
+            return Err(AptosError::InvalidSignature)
+        },
+    };
+
+    return Ok(serializer.finish());
+}
+
+/// 0x prefixed hex strings
+fn json_hex(value: &serde_json::Value) -> Result<Vec<u8>, AptosError> {
+    return match hex::decode(value.as_str().unwrap_or("").trim_start_matches("0x")) {
+        Ok(bytes) => Ok(bytes),
+        Err(error) => {
+            log::error!("{}", error);
+            Err(AptosError::InvalidSignature)
+        },
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBLIC_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+
+    fn signature() -> String {
+        return "22".repeat(64);
+    }
+
+    fn transaction(arguments: Vec<MoveValue>) -> String {
+        return serde_json::json!({
+            "sender": "0x0a550c18",
+            "sequence_number": "7",
+            "max_gas_amount": "1000",
+            "gas_unit_price": "1",
+            "expiration_timestamp_secs": "1660000000",
+            "payload": EntryFunction::new("0x1::coin::transfer", Vec::new(), arguments).unwrap().to_payload(),
+        }).to_string();
+    }
+
+    fn transfer() -> String {
+        return transaction(vec![MoveValue::Address(AccountAddress::parse("0xdd").unwrap()), MoveValue::U64(100)]);
+    }
+
+    fn transfer_types() -> Vec<TypeTag> {
+        return vec![TypeTag::Address, TypeTag::U64];
+    }
+
+    fn signature_payload() -> serde_json::Value {
+        return serde_json::json!({
+            "type": "ed25519_signature",
+            "public_key": format!("0x{}", PUBLIC_KEY),
+            "signature": format!("0x{}", signature()),
+        });
+    }
+
+    #[test]
+    fn transaction_salt() {
+        // sha3_256("APTOS::Transaction")
+        assert_eq!(hex::encode(sha3_256(TRANSACTION_SALT)), "fa210a9417ef3e7fa45bfa1d17a8dbd4d883711910a550d265fee189e9266dd4");
+    }
+
+    #[test]
+    fn ed25519_authenticator_layout() {
+        let authenticator = authenticator_bcs(&signature_payload()).unwrap();
+
+        assert_eq!(hex::encode(authenticator), format!("0020{}40{}", PUBLIC_KEY, signature()));
+    }
+
+    #[test]
+    fn multi_ed25519_authenticator_layout() {
+        let signature_payload = serde_json::json!({
+            "type": "multi_ed25519_signature",
+            "public_keys": [format!("0x{}", PUBLIC_KEY), format!("0x{}", "33".repeat(32))],
+            "signatures": [format!("0x{}", signature())],
+            "threshold": 1,
+            "bitmap": "0x80000000",
+        });
+
+        let authenticator = authenticator_bcs(&signature_payload).unwrap();
+
+        // Keys and threshold, then signatures and bitmap, each one prefixed by its length
+        assert_eq!(
+            hex::encode(authenticator),
+            format!("0141{}{}0144{}80000000", PUBLIC_KEY, "33".repeat(32), signature()),
+        );
+    }
+
+    #[test]
+    fn hash_of_a_signed_transfer() {
+        // sha3_256(sha3_256("APTOS::Transaction") || 0 || raw transaction || authenticator), computed independently
+        assert_eq!(
+            transaction_hash(&transfer(), &signature_payload(), 4, &transfer_types()).unwrap(),
+            "0xa9de58efee7b9a3cc57f056b3369281766d655b5f43a5e038b6c556885b13702",
+        );
+    }
+
+    #[test]
+    fn hash_depends_on_the_argument_types() {
+        let as_u64 = transaction_hash(&transaction(vec![MoveValue::U64(1)]), &signature_payload(), 4, &[TypeTag::U64]).unwrap();
+        let as_u8 = transaction_hash(&transaction(vec![MoveValue::U8(1)]), &signature_payload(), 4, &[TypeTag::U8]).unwrap();
+
+        assert_ne!(as_u64, as_u8);
+    }
+
+    #[test]
+    fn rejects_unsupported_signatures() {
+        let signature_payload = serde_json::json!({"type": "secp256k1_ecdsa_signature"});
+
+        assert!(matches!(transaction_hash(&transfer(), &signature_payload, 4, &transfer_types()), Err(AptosError::InvalidSignature)));
+    }
+}
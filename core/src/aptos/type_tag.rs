@@ -0,0 +1,388 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::aptos::*;
+use crate::aptos::bcs::BcsSerializer;
+
+/// Move type, as given in the type arguments of a function or in a resource type
+/// Specs here https://github.com/move-language/move/blob/main/language/move-core/types/src/language_storage.rs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeTag {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    Address,
+    Signer,
+    Vector(Box<TypeTag>),
+    Struct(StructTag),
+}
+
+/// `address::module::Name<type_params>`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StructTag {
+    pub address: AccountAddress,
+    pub module: String,
+    pub name: String,
+    pub type_params: Vec<TypeTag>,
+}
+
+impl TypeTag {
+    pub fn parse(type_tag: &str) -> Result<Self, AptosError> {
+        return match Self::validate(type_tag) {
+            Ok(parsed) => Ok(parsed),
+            Err(reason) => {
+                log::error!("Invalid type {}: {}", type_tag, reason);
+                Err(AptosError::InvalidTypeTag)
+            },
+        };
+    }
+
+    /// Same as `parse` but returns the reason why the type is invalid
+    pub fn validate(type_tag: &str) -> Result<Self, String> {
+        let mut parser = TypeTagParser {
+            input: type_tag,
+            position: 0,
+        };
+
+        let parsed = match parser.type_tag() {
+            Ok(parsed) => parsed,
+            Err(reason) => return Err(reason),
+        };
+
+        if !parser.at_end() {
+            return Err(format!("unexpected {}", parser.rest()));
+        }
+
+        return Ok(parsed);
+    }
+
+    /// Percent encoded form, to be used as a path segment of the REST API
+    pub fn to_url_path(&self) -> String {
+        return url_encode(&self.to_string());
+    }
+
+    /// BCS encoding of the type as found in entry function payloads
+    pub fn serialize(&self, serializer: &mut BcsSerializer) {
+        match self {
+            Self::Bool => { serializer.uleb128(0); },
+            Self::U8 => { serializer.uleb128(1); },
+            Self::U64 => { serializer.uleb128(2); },
+            Self::U128 => { serializer.uleb128(3); },
+            Self::Address => { serializer.uleb128(4); },
+            Self::Signer => { serializer.uleb128(5); },
+            Self::Vector(element) => {
+                serializer.uleb128(6);
+                element.serialize(serializer);
+            },
+            Self::Struct(struct_tag) => {
+                serializer.uleb128(7);
+                struct_tag.serialize(serializer);
+            },
+            Self::U16 => { serializer.uleb128(8); },
+            Self::U32 => { serializer.uleb128(9); },
+            Self::U256 => { serializer.uleb128(10); },
+        };
+    }
+}
+
+impl StructTag {
+    /// Parses a struct type such as a resource type, primitive types are rejected
+    pub fn parse(struct_tag: &str) -> Result<Self, AptosError> {
+        return match TypeTag::parse(struct_tag) {
+            Ok(TypeTag::Struct(struct_tag)) => Ok(struct_tag),
+            Ok(_) => {
+                log::error!("{} is not a struct", struct_tag);
+                Err(AptosError::InvalidTypeTag)
+            },
+            Err(error) => Err(error),
+        };
+    }
+
+    pub fn to_url_path(&self) -> String {
+        return url_encode(&self.to_string());
+    }
+
+    /// True for the struct `0x1::module::name` of the framework
+    pub fn is_framework(&self, module: &str, name: &str) -> bool {
+        return self.address.to_short_string() == "0x1" && self.module == module && self.name == name;
+    }
+
+    pub fn serialize(&self, serializer: &mut BcsSerializer) {
+        serializer
+            .fixed_bytes(self.address.as_bytes())
+            .str(&self.module)
+            .str(&self.name)
+            .uleb128(self.type_params.len() as u64);
+
+        for type_param in &self.type_params {
+            type_param.serialize(serializer);
+        }
+    }
+}
+
+/// Printed with short addresses, the form used by the node
+impl fmt::Display for TypeTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::Bool => write!(f, "bool"),
+            Self::U8 => write!(f, "u8"),
+            Self::U16 => write!(f, "u16"),
+            Self::U32 => write!(f, "u32"),
+            Self::U64 => write!(f, "u64"),
+            Self::U128 => write!(f, "u128"),
+            Self::U256 => write!(f, "u256"),
+            Self::Address => write!(f, "address"),
+            Self::Signer => write!(f, "signer"),
+            Self::Vector(element) => write!(f, "vector<{}>", element),
+            Self::Struct(struct_tag) => write!(f, "{}", struct_tag),
+        };
+    }
+}
+
+impl fmt::Display for StructTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Err(error) = write!(f, "{}::{}::{}", self.address.to_short_string(), self.module, self.name) {
+            return Err(error);
+        }
+
+        if self.type_params.is_empty() {
+            return Ok(());
+        }
+
+        let type_params: Vec<String> = self.type_params.iter().map(|type_param| type_param.to_string()).collect();
+
+        return write!(f, "<{}>", type_params.join(", "));
+    }
+}
+
+impl FromStr for TypeTag {
+    type Err = AptosError;
+
+    fn from_str(type_tag: &str) -> Result<Self, AptosError> {
+        return Self::parse(type_tag);
+    }
+}
+
+impl FromStr for StructTag {
+    type Err = AptosError;
+
+    fn from_str(struct_tag: &str) -> Result<Self, AptosError> {
+        return Self::parse(struct_tag);
+    }
+}
+
+/// Keeps the unreserved characters and the `::` separators, generics and spaces are escaped
+fn url_encode(value: &str) -> String {
+    return value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b':' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+}
+
+/// Recursive descent parser, whitespace is allowed around the separators
+struct TypeTagParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> TypeTagParser<'a> {
+    fn type_tag(&mut self) -> Result<TypeTag, String> {
+        let identifier = match self.identifier() {
+            Ok(identifier) => identifier,
+            Err(reason) => return Err(reason),
+        };
+
+        let type_tag = match identifier {
+            "bool" => TypeTag::Bool,
+            "u8" => TypeTag::U8,
+            "u16" => TypeTag::U16,
+            "u32" => TypeTag::U32,
+            "u64" => TypeTag::U64,
+            "u128" => TypeTag::U128,
+            "u256" => TypeTag::U256,
+            "address" => TypeTag::Address,
+            "signer" => TypeTag::Signer,
+            "vector" => {
+                let mut type_params = match self.type_params() {
+                    Ok(type_params) => type_params,
+                    Err(reason) => return Err(reason),
+                };
+
+                if type_params.len() != 1 {
+                    return Err("vector takes one type parameter".to_string());
+                }
+
+                TypeTag::Vector(Box::new(type_params.remove(0)))
+            },
+            address => match self.struct_tag(address) {
+                Ok(struct_tag) => TypeTag::Struct(struct_tag),
+                Err(reason) => return Err(reason),
+            },
+        };
+
+        return Ok(type_tag);
+    }
+
+    fn struct_tag(&mut self, address: &str) -> Result<StructTag, String> {
+        let address = match AccountAddress::validate(address) {
+            Ok(address) => address,
+            Err(reason) => return Err(format!("{} {}", reason, address)),
+        };
+
+        let mut names: Vec<String> = Vec::new();
+
+        for _ in 0..2 {
+            if !self.consume("::") {
+                return Err(format!("expected :: at {}", self.rest()));
+            }
+
+            match self.identifier() {
+                Ok(name) => names.push(name.to_string()),
+                Err(reason) => return Err(reason),
+            }
+        }
+
+        let type_params = match self.peek("<") {
+            true => match self.type_params() {
+                Ok(type_params) => type_params,
+                Err(reason) => return Err(reason),
+            },
+            false => Vec::new(),
+        };
+
+        return Ok(StructTag {
+            address: address,
+            module: names.remove(0),
+            name: names.remove(0),
+            type_params: type_params,
+        });
+    }
+
+    fn type_params(&mut self) -> Result<Vec<TypeTag>, String> {
+        if !self.consume("<") {
+            return Err(format!("expected < at {}", self.rest()));
+        }
+
+        let mut type_params: Vec<TypeTag> = Vec::new();
+
+        loop {
+            match self.type_tag() {
+                Ok(type_param) => type_params.push(type_param),
+                Err(reason) => return Err(reason),
+            }
+
+            if self.consume(",") {
+                continue;
+            }
+
+            if self.consume(">") {
+                return Ok(type_params);
+            }
+
+            return Err(format!("expected , or > at {}", self.rest()));
+        }
+    }
+
+    /// Identifier or address
+    fn identifier(&mut self) -> Result<&'a str, String> {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        let length = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+
+        if length == 0 {
+            return Err(format!("expected an identifier at {}", rest));
+        }
+
+        self.position += length;
+
+        return Ok(&rest[..length]);
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        let found = self.peek(token);
+
+        if found {
+            self.position += token.len();
+        }
+
+        return found;
+    }
+
+    fn peek(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+
+        return self.rest().starts_with(token);
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+
+        return self.rest().is_empty();
+    }
+
+    fn rest(&self) -> &'a str {
+        return &self.input[self.position..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COIN_STORE: &str = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>";
+
+    #[test]
+    fn nested_generics_round_trip() {
+        for type_tag in [COIN_STORE, "vector<vector<u8>>", "0x1::table::Table<address, vector<0x1::string::String>>"] {
+            assert_eq!(TypeTag::parse(type_tag).unwrap().to_string(), type_tag);
+        }
+
+        assert_eq!(
+            TypeTag::parse("vector<vector<u8>>").unwrap(),
+            TypeTag::Vector(Box::new(TypeTag::Vector(Box::new(TypeTag::U8)))),
+        );
+    }
+
+    #[test]
+    fn long_addresses_are_printed_short() {
+        let long_form = format!("0x{:0>64}::coin::CoinStore<{:0>64}::aptos_coin::AptosCoin>", "1", "1");
+        let coin_store = TypeTag::parse(&long_form).unwrap();
+
+        assert_eq!(coin_store, TypeTag::parse(COIN_STORE).unwrap());
+        assert_eq!(coin_store.to_string(), COIN_STORE);
+        assert_eq!(coin_store.to_url_path(), "0x1::coin::CoinStore%3C0x1::aptos_coin::AptosCoin%3E");
+    }
+
+    #[test]
+    fn whitespace_is_allowed_around_separators() {
+        let coin_store = TypeTag::parse(" 0x1 :: coin :: CoinStore < 0x1::aptos_coin::AptosCoin > ").unwrap();
+
+        assert_eq!(coin_store.to_string(), COIN_STORE);
+    }
+
+    #[test]
+    fn rejects_malformed_types() {
+        assert_eq!(TypeTag::validate("0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin").unwrap_err(), "expected , or > at ");
+        assert_eq!(TypeTag::validate("0x1::coin::CoinStore<u8>>").unwrap_err(), "unexpected >");
+        assert_eq!(TypeTag::validate("0x1::::CoinStore").unwrap_err(), "expected an identifier at ::CoinStore");
+        assert_eq!(TypeTag::validate("vector<u8,>").unwrap_err(), "expected an identifier at >");
+        assert_eq!(TypeTag::validate("vector<u8, u64>").unwrap_err(), "vector takes one type parameter");
+        assert_eq!(TypeTag::validate("0x1::coin").unwrap_err(), "expected :: at ");
+        assert!(TypeTag::validate("").is_err());
+        assert!(matches!(TypeTag::parse("0xg::coin::Coin"), Err(AptosError::InvalidTypeTag)));
+        assert!(matches!(StructTag::parse("vector<u8>"), Err(AptosError::InvalidTypeTag)));
+    }
+}
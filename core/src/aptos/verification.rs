@@ -0,0 +1,37 @@
+use std::convert::TryFrom;
+use ed25519_dalek::{PublicKey, Signature};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureStatus {
+    Valid,
+    /// The signature does not match the public key and the message
+    Invalid,
+    /// The public key or the signature can't be decoded
+    Malformed,
+    /// The public key is not one of the account
+    UnknownSigner,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignatureVerification {
+    pub public_key: String,
+    pub status: SignatureStatus,
+}
+
+/// Checks a hex encoded ed25519 signature of the message
+pub fn verify_signature(public_key: &str, message: &[u8], signature: &str) -> SignatureStatus {
+    let public_key = match hex::decode(public_key.trim_start_matches("0x")).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+        Some(public_key) => public_key,
+        None => return SignatureStatus::Malformed,
+    };
+
+    let signature = match hex::decode(signature.trim_start_matches("0x")).ok().and_then(|bytes| Signature::try_from(&bytes[..]).ok()) {
+        Some(signature) => signature,
+        None => return SignatureStatus::Malformed,
+    };
+
+    return match public_key.verify_strict(message, &signature) {
+        Ok(()) => SignatureStatus::Valid,
+        Err(_) => SignatureStatus::Invalid,
+    };
+}
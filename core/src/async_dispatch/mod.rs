@@ -8,7 +8,7 @@ use crate::requests::*;
 use crate::rust_data::RustData;
 
 lazy_static::lazy_static! {
-    static ref RUNTIME: tokio::runtime::Runtime = {
+    pub static ref RUNTIME: tokio::runtime::Runtime = {
         tokio::runtime::Runtime::new().expect("Failed to create tokio runtime")
     };
 }
@@ -20,6 +20,8 @@ pub struct RustCallback {
     pub callback: extern "C" fn(*const c_void, RustData),
 }
 unsafe impl Send for RustCallback {}
+// The event callback is shared between the tasks of the runtime, the host side must be thread safe
+unsafe impl Sync for RustCallback {}
 
 impl RustCallback {
     pub fn new(swift_callback_ptr: *const c_void, callback_ptr: *const c_void) -> Self {
@@ -33,6 +35,11 @@ impl RustCallback {
     pub fn run(self, response_data: RustData) {
         (self.callback)(self.swift_callback_ptr, response_data);
     }
+
+    /// Same as `run` but the callback can be called several times (used for events)
+    pub fn call(&self, response_data: RustData) {
+        (self.callback)(self.swift_callback_ptr, response_data);
+    }
 }
 
 unsafe impl Send for Core {}
@@ -41,10 +48,14 @@ unsafe impl Sync for Core {}
 pub fn dispatch_request_async(core: *const Core, request: Request, callback: RustCallback) {
     assert!(!core.is_null());
 
-    let core_arc = unsafe { Arc::from_raw(core) };
+    // The host keeps its own reference until `free_core`
+    let core_arc = unsafe {
+        Arc::increment_strong_count(core);
+        Arc::from_raw(core)
+    };
 
     RUNTIME.spawn(async move {        
-        use crate::core_proto::request::AsyncRequests::{GetAsyncBacktrace, FundWallet, GetWalletBalance, CreateWalletTransaction, SignWalletTransaction, SubmitWalletTransaction, GetWalletTransactions};
+        use crate::core_proto::request::AsyncRequests::{GetAsyncBacktrace, FundWallet, GetWalletBalance, CreateWalletTransaction, SignWalletTransaction, SubmitWalletTransaction, GetWalletTransactions, Transfer, FinalizeTransactionBundle, VerifyWalletSignatures, RotateAuthenticationKey, ExportTransactions, PublishPackage, RegisterCoin, IsCoinRegistered, BatchTransfer, ReplaceTransaction, GetTransactionHash, CreateTransactionBundle};
 
         let bytes = match request.async_requests {
             Some(req) => {
@@ -56,6 +67,18 @@ pub fn dispatch_request_async(core: *const Core, request: Request, callback: Rus
                     SignWalletTransaction(sign_transaction_req) => handle_sign_wallet_transaction(core_arc, sign_transaction_req).await.encode_to_vec(),
                     SubmitWalletTransaction(submit_transaction_req) => handle_submit_wallet_transaction(core_arc, submit_transaction_req).await.encode_to_vec(),
                     GetWalletTransactions(get_wallet_transactions_req) => handle_get_wallet_transactions(core_arc, get_wallet_transactions_req).await.encode_to_vec(),
+                    Transfer(transfer_req) => handle_transfer(core_arc, transfer_req).await.encode_to_vec(),
+                    FinalizeTransactionBundle(finalize_bundle_req) => handle_finalize_transaction_bundle(core_arc, finalize_bundle_req).await.encode_to_vec(),
+                    VerifyWalletSignatures(verify_signatures_req) => handle_verify_wallet_signatures(core_arc, verify_signatures_req).await.encode_to_vec(),
+                    RotateAuthenticationKey(rotate_key_req) => handle_rotate_authentication_key(core_arc, rotate_key_req).await.encode_to_vec(),
+                    ExportTransactions(export_transactions_req) => handle_export_transactions(core_arc, export_transactions_req).await.encode_to_vec(),
+                    PublishPackage(publish_package_req) => handle_publish_package(core_arc, publish_package_req).await.encode_to_vec(),
+                    RegisterCoin(register_coin_req) => handle_register_coin(core_arc, register_coin_req).await.encode_to_vec(),
+                    IsCoinRegistered(is_coin_registered_req) => handle_is_coin_registered(core_arc, is_coin_registered_req).await.encode_to_vec(),
+                    BatchTransfer(batch_transfer_req) => handle_batch_transfer(core_arc, batch_transfer_req).await.encode_to_vec(),
+                    ReplaceTransaction(replace_transaction_req) => handle_replace_transaction(core_arc, replace_transaction_req).await.encode_to_vec(),
+                    GetTransactionHash(get_transaction_hash_req) => handle_get_transaction_hash(core_arc, get_transaction_hash_req).await.encode_to_vec(),
+                    CreateTransactionBundle(create_bundle_req) => handle_create_transaction_bundle(core_arc, create_bundle_req).await.encode_to_vec(),
                 }
             },
             None => return log::error!("Unhandled asynchronous request"),
@@ -0,0 +1,243 @@
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+use crate::storage::{now_secs, Storage};
+
+const CONTACTS_KEY: &str = "contacts";
+
+/// Named address of the address book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub label: String,
+    pub address: AccountAddress,
+    pub notes: String,
+    /// REST URL of the node the address is used on
+    pub network: String,
+    /// Unix timestamps in seconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Labels of the addresses the user sends to, unique per network.
+/// Every change is written to the storage, unless the stored contacts could not be read.
+pub struct AddressBook {
+    contacts: RwLock<Vec<Contact>>,
+    storage: Arc<Storage>,
+    network: String,
+    /// False when the stored contacts are invalid, they are kept as is until they are loaded
+    writable: AtomicBool,
+}
+
+impl AddressBook {
+    pub fn new(storage: Arc<Storage>, network: String) -> Self {
+        return Self {
+            contacts: RwLock::new(Vec::new()),
+            storage: storage,
+            network: network,
+            writable: AtomicBool::new(true),
+        };
+    }
+
+    /// Reads the stored contacts once the storage is opened, contacts added before are kept.
+    /// Nothing is written when the stored contacts are invalid.
+    pub fn load(&self) -> Result<usize, AptosError> {
+        let mut contacts = self.contacts.write().unwrap();
+
+        let stored_contacts: Vec<Contact> = match self.storage.read(CONTACTS_KEY) {
+            Ok(stored_contacts) => stored_contacts.unwrap_or_default(),
+            Err(error) => {
+                self.writable.store(false, Ordering::SeqCst);
+                return Err(error)
+            },
+        };
+
+        self.writable.store(true, Ordering::SeqCst);
+
+        for contact in stored_contacts {
+            if !contacts.iter().any(|c| c.network == contact.network && c.label == contact.label) {
+                contacts.push(contact);
+            }
+        }
+
+        return match self.save(&contacts) {
+            Ok(()) => Ok(contacts.len()),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Adds a contact on the given network, the network of the core when empty
+    pub fn add(&self, label: &str, address: AccountAddress, notes: &str, network: &str) -> Result<Contact, AptosError> {
+        let network = match network.is_empty() {
+            true => self.network.clone(),
+            false => network.to_string(),
+        };
+
+        let mut contacts = self.contacts.write().unwrap();
+
+        if contacts.iter().any(|c| c.network == network && c.label == label) {
+            log::error!("Contact {} already exists", label);
+            return Err(AptosError::ContactExists);
+        }
+
+        let now = now_secs();
+
+        let contact = Contact {
+            label: label.to_string(),
+            address: address,
+            notes: notes.to_string(),
+            network: network,
+            created_at: now,
+            updated_at: now,
+        };
+
+        contacts.push(contact.clone());
+
+        return self.save(&contacts).map(|()| contact);
+    }
+
+    /// Changes the label, address or notes of a contact of the core network, the fields left `None` are kept
+    pub fn update(
+        &self,
+        label: &str,
+        new_label: Option<&str>,
+        address: Option<AccountAddress>,
+        notes: Option<&str>,
+    ) -> Result<Contact, AptosError> {
+        let mut contacts = self.contacts.write().unwrap();
+
+        if let Some(new_label) = new_label {
+            if new_label != label && contacts.iter().any(|c| c.network == self.network && c.label == new_label) {
+                log::error!("Contact {} already exists", new_label);
+                return Err(AptosError::ContactExists);
+            }
+        }
+
+        let contact = match contacts.iter_mut().find(|c| c.network == self.network && c.label == label) {
+            Some(contact) => contact,
+            None => {
+                log::error!("Unknown contact {}", label);
+                return Err(AptosError::UnknownContact)
+            },
+        };
+
+        if let Some(new_label) = new_label {
+            contact.label = new_label.to_string();
+        }
+
+        if let Some(address) = address {
+            contact.address = address;
+        }
+
+        if let Some(notes) = notes {
+            contact.notes = notes.to_string();
+        }
+
+        contact.updated_at = now_secs();
+
+        let contact = contact.clone();
+
+        return self.save(&contacts).map(|()| contact);
+    }
+
+    /// Returns false when there is no such contact on the core network
+    pub fn remove(&self, label: &str) -> Result<bool, AptosError> {
+        let mut contacts = self.contacts.write().unwrap();
+        let count = contacts.len();
+
+        contacts.retain(|c| !(c.network == self.network && c.label == label));
+
+        return match contacts.len() != count {
+            true => self.save(&contacts).map(|()| true),
+            false => Ok(false),
+        };
+    }
+
+    /// Returns the contacts of the core network whose label or address starts with the prefix, all of them when empty
+    /// Labels are matched ignoring the case, addresses with or without the `0x` prefix
+    pub fn find(&self, prefix: &str) -> Vec<Contact> {
+        let label_prefix = prefix.to_lowercase();
+        let address_prefix = format!("0x{}", label_prefix.trim_start_matches("0x"));
+
+        let mut contacts: Vec<Contact> = self.contacts.read().unwrap()
+            .iter()
+            .filter(|c| c.network == self.network)
+            .filter(|c| prefix.is_empty()
+                || c.label.to_lowercase().starts_with(&label_prefix)
+                || c.address.to_string().starts_with(&address_prefix)
+                || c.address.to_short_string().starts_with(&address_prefix))
+            .cloned()
+            .collect();
+
+        contacts.sort_by(|a, b| a.label.to_lowercase().cmp(&b.label.to_lowercase()));
+
+        return contacts;
+    }
+
+    /// Returns the label of the address on the core network
+    pub fn label_of(&self, address: &AccountAddress) -> Option<String> {
+        return self.contacts.read().unwrap()
+            .iter()
+            .find(|c| c.network == self.network && c.address == *address)
+            .map(|c| c.label.clone());
+    }
+
+    /// Called with the lock held, so that the last write has the last changes
+    fn save(&self, contacts: &[Contact]) -> Result<(), AptosError> {
+        if !self.writable.load(Ordering::SeqCst) {
+            log::error!("Contacts not saved, the stored ones are invalid");
+            return Err(AptosError::StorageFailed);
+        }
+
+        return self.storage.set(CONTACTS_KEY, &contacts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORK: &str = "http://localhost";
+
+    fn address_book() -> AddressBook {
+        return AddressBook::new(Arc::new(Storage::new()), NETWORK.to_string());
+    }
+
+    #[test]
+    fn labels_are_unique_per_network() {
+        let address_book = address_book();
+        let address = AccountAddress::parse("0xa").unwrap();
+
+        address_book.add("alice", address, "", "").unwrap();
+        address_book.add("bob", address, "", "").unwrap();
+
+        assert!(matches!(address_book.add("alice", address, "", ""), Err(AptosError::ContactExists)));
+        assert!(matches!(address_book.update("bob", Some("alice"), None, None), Err(AptosError::ContactExists)));
+        assert!(matches!(address_book.update("carol", None, None, None), Err(AptosError::UnknownContact)));
+
+        address_book.add("alice", address, "", "http://other").unwrap();
+    }
+
+    #[test]
+    fn notes_can_be_cleared() {
+        let address_book = address_book();
+
+        address_book.add("alice", AccountAddress::parse("0xa").unwrap(), "friend", "").unwrap();
+
+        assert_eq!(address_book.update("alice", None, None, None).unwrap().notes, "friend");
+        assert_eq!(address_book.update("alice", None, None, Some("")).unwrap().notes, "");
+    }
+
+    #[test]
+    fn invalid_stored_contacts_are_not_overwritten() {
+        let address_book = address_book();
+        address_book.storage.set(CONTACTS_KEY, &serde_json::json!({"alice": "0xa"})).unwrap();
+
+        assert!(matches!(address_book.load(), Err(AptosError::StorageFailed)));
+        assert!(matches!(address_book.add("bob", AccountAddress::parse("0xb").unwrap(), "", ""), Err(AptosError::StorageFailed)));
+
+        let stored: serde_json::Value = address_book.storage.get(CONTACTS_KEY).unwrap();
+        assert_eq!(stored, serde_json::json!({"alice": "0xa"}));
+    }
+}
@@ -1,21 +1,141 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
 use crate::aptos::*;
+use crate::async_dispatch::RustCallback;
+use crate::events::EventEmitter;
+use crate::watcher::AccountWatcher;
+use crate::registry::AccountRegistry;
+use crate::storage::Storage;
+use crate::history::TransactionHistory;
+use crate::contacts::AddressBook;
+use crate::sequence::SequenceNumberManager;
 
 pub struct Core {
     pub aptos_rest_client: AptosRestClient,
     pub aptos_faucet_client: AptosFaucetClient,
+    pub event_emitter: Arc<EventEmitter>,
+    pub account_watcher: AccountWatcher,
+    /// Set by the host to sign with keys it owns
+    pub signer_callback: RwLock<Option<RustSignerCallback>>,
+    pub storage: Arc<Storage>,
+    pub account_registry: AccountRegistry,
+    pub transaction_history: TransactionHistory,
+    pub address_book: AddressBook,
+    pub module_abis: ModuleAbiCache,
+    pub sequence_numbers: SequenceNumberManager,
+    /// Tasks reporting the confirmation of the submitted transactions by hash, removed once done
+    pub confirmation_watches: Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
 }
 
 impl Core {
     pub fn new(
         aptos_rest_url: String,
         aptos_faucet_url: String,
+        event_callback: RustCallback,
+    ) -> Core {
+        return Self::with_clock(aptos_rest_url, aptos_faucet_url, event_callback, Box::new(SystemClock));
+    }
+
+    /// Same as `new` with the local clock given, to simulate a device clock ahead or behind the chain
+    pub fn with_clock(
+        aptos_rest_url: String,
+        aptos_faucet_url: String,
+        event_callback: RustCallback,
+        clock: Box<dyn Clock>,
     ) -> Core {
-        let rest_client = AptosRestClient::new(aptos_rest_url);
+        let storage = Arc::new(Storage::new());
+        let account_registry = AccountRegistry::new(storage.clone(), aptos_rest_url.clone());
+        let transaction_history = TransactionHistory::new(storage.clone(), aptos_rest_url.clone());
+        let address_book = AddressBook::new(storage.clone(), aptos_rest_url.clone());
+        let rest_client = AptosRestClient::with_clock(aptos_rest_url, clock);
         let faucet_client = AptosFaucetClient::new(aptos_faucet_url);
+        let event_emitter = Arc::new(EventEmitter::new(event_callback));
+        let account_watcher = AccountWatcher::new(rest_client.clone(), event_emitter.clone());
+        let sequence_numbers = SequenceNumberManager::new(rest_client.clone(), event_emitter.clone());
 
         return Self {
             aptos_rest_client: rest_client,
             aptos_faucet_client: faucet_client,
+            event_emitter: event_emitter,
+            account_watcher: account_watcher,
+            signer_callback: RwLock::new(None),
+            storage: storage,
+            account_registry: account_registry,
+            transaction_history: transaction_history,
+            address_book: address_book,
+            module_abis: ModuleAbiCache::new(),
+            sequence_numbers: sequence_numbers,
+            confirmation_watches: Mutex::new(HashMap::new()),
         }
     }
-}
\ No newline at end of file
+
+    /// Stops the watcher, the sequence number sync and the confirmation watches, called when the core is freed.
+    /// The requests still running end on their own.
+    pub fn stop(&self) {
+        self.account_watcher.stop();
+        self.sequence_numbers.stop();
+
+        for (_, task) in self.confirmation_watches.lock().unwrap().drain() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::async_dispatch::RUNTIME;
+    use crate::rust_data::RustData;
+
+    const LEDGER_SECS: u64 = 1_700_000_000;
+    // Nothing listens on the discard port, the ledger info can't be fetched
+    const NODE_URL: &str = "http://127.0.0.1:9";
+
+    extern "C" fn ignore_event(_: *const std::ffi::c_void, _: RustData) {}
+
+    #[test]
+    fn transactions_expire_with_the_chain_time_of_the_injected_clock() {
+        let (clock, time) = FakeClock::new(LEDGER_SECS + 3600);
+
+        let event_callback = RustCallback {
+            swift_callback_ptr: std::ptr::null(),
+            callback: ignore_event,
+        };
+
+        let core = Core::with_clock(NODE_URL.to_string(), NODE_URL.to_string(), event_callback, clock);
+        core.aptos_rest_client.clock().observe(&(LEDGER_SECS * 1_000_000).to_string()).unwrap();
+
+        // Measured recently, the ledger info is not fetched again
+        RUNTIME.block_on(core.aptos_rest_client.ensure_clock_synced()).unwrap();
+
+        let address = AccountAddress::parse("0xa").unwrap();
+        let transaction = core.aptos_rest_client.generate_transaction_at(&address, serde_json::json!({}), 0, 1000, 1).unwrap();
+        let transaction: serde_json::Value = serde_json::from_str(&transaction).unwrap();
+
+        assert_eq!(transaction["expiration_timestamp_secs"], (LEDGER_SECS + TRANSACTION_EXPIRATION_SECS).to_string());
+
+        // The device clock drifts, the offset is measured again after a while
+        *time.write().unwrap() = Some(Duration::from_secs(LEDGER_SECS + 3900));
+
+        assert!(matches!(RUNTIME.block_on(core.aptos_rest_client.ensure_clock_synced()), Err(AptosError::InvalidRequest)));
+    }
+
+    #[test]
+    fn stop_aborts_the_confirmation_watches() {
+        let event_callback = RustCallback {
+            swift_callback_ptr: std::ptr::null(),
+            callback: ignore_event,
+        };
+
+        let core = Core::new(NODE_URL.to_string(), NODE_URL.to_string(), event_callback);
+        let task = RUNTIME.spawn(async { tokio::time::sleep(Duration::from_secs(3600)).await });
+        core.confirmation_watches.lock().unwrap().insert("0x1".to_string(), task);
+
+        core.stop();
+
+        assert!(core.confirmation_watches.lock().unwrap().is_empty());
+    }
+}
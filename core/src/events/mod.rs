@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, RwLock};
+use prost::Message;
+
+use crate::async_dispatch::RustCallback;
+use crate::core_proto::*;
+use crate::rust_data::RustData;
+
+/// Pushes `Event` messages to the host through the long-lived callback registered at `create_core`.
+/// Only events whose topic has been subscribed to are sent.
+pub struct EventEmitter {
+    callback: RustCallback,
+    topics: Mutex<HashSet<EventTopic>>,
+    /// Read while the host is called, written by `close`
+    closed: RwLock<bool>,
+}
+
+impl EventEmitter {
+    pub fn new(callback: RustCallback) -> Self {
+        return Self {
+            callback: callback,
+            topics: Mutex::new(HashSet::new()),
+            closed: RwLock::new(false),
+        };
+    }
+
+    /// Adds the given topics to the subscriptions, returns the active topics
+    pub fn subscribe(&self, topics: &[EventTopic]) -> Vec<EventTopic> {
+        let mut active_topics = self.topics.lock().unwrap();
+
+        for topic in topics {
+            if *topic != EventTopic::UnknownTopic {
+                active_topics.insert(*topic);
+            }
+        }
+
+        return active_topics.iter().cloned().collect();
+    }
+
+    /// Removes the given topics from the subscriptions, returns the active topics
+    pub fn unsubscribe(&self, topics: &[EventTopic]) -> Vec<EventTopic> {
+        let mut active_topics = self.topics.lock().unwrap();
+
+        for topic in topics {
+            active_topics.remove(topic);
+        }
+
+        return active_topics.iter().cloned().collect();
+    }
+
+    pub fn is_subscribed(&self, topic: EventTopic) -> bool {
+        return self.topics.lock().unwrap().contains(&topic);
+    }
+
+    /// Stops sending events, the host callback must not be called once the core is freed.
+    /// Waits for the events being sent to return from the host, it must not be called from the event callback.
+    pub fn close(&self) {
+        *self.closed.write().unwrap() = true;
+    }
+
+    /// Sends the event to the host if its topic is subscribed
+    pub fn emit(&self, kind: event::Kind) {
+        // Held until the host returns, the core can't be freed in the middle of the call
+        let closed = self.closed.read().unwrap();
+
+        if *closed || !self.is_subscribed(topic(&kind)) {
+            return;
+        }
+
+        let event = Event {
+            kind: Some(kind),
+        };
+
+        self.callback.call(RustData::from(event.encode_to_vec()));
+    }
+}
+
+/// Returns the topic an event is published on
+pub fn topic(kind: &event::Kind) -> EventTopic {
+    return match kind {
+        event::Kind::IncomingTransfer(_) => EventTopic::IncomingTransfer,
+        event::Kind::TransactionConfirmed(_) => EventTopic::TransactionConfirmed,
+        event::Kind::NodeSwitched(_) => EventTopic::NodeSwitched,
+        event::Kind::Log(_) => EventTopic::Log,
+        event::Kind::BalanceChanged(_) => EventTopic::BalanceChanged,
+        event::Kind::NewTransaction(_) => EventTopic::NewTransaction,
+        event::Kind::SequenceNumberChanged(_) => EventTopic::SequenceNumberChanged,
+        event::Kind::TransferProgress(_) => EventTopic::TransferProgress,
+        event::Kind::TransactionExpired(_) => EventTopic::TransactionExpired,
+    };
+}
+
+/// Converts protobuf enum values to topics, unknown values are dropped
+pub fn topics_from_i32(values: &[i32]) -> Vec<EventTopic> {
+    return values.iter().filter_map(|value| EventTopic::from_i32(*value)).collect();
+}
+
+/// Converts topics to protobuf enum values
+pub fn topics_to_i32(topics: &[EventTopic]) -> Vec<i32> {
+    return topics.iter().map(|topic| *topic as i32).collect();
+}
@@ -0,0 +1,152 @@
+use std::path::Path;
+use serde::Serialize;
+
+use crate::aptos::*;
+use crate::history::CachedTransaction;
+
+const CSV_HEADER: &str = "timestamp,hash,direction,counterparty,amount,coin_type,gas_fee,status,version,sequence_number";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// One line of a statement, from the point of view of the exported account
+#[derive(Debug, Serialize)]
+struct StatementLine {
+    /// Unix timestamp in seconds
+    timestamp: u64,
+    hash: String,
+    direction: &'static str,
+    counterparty: String,
+    amount: u64,
+    coin_type: String,
+    gas_fee: u64,
+    status: &'static str,
+    version: u64,
+    sequence_number: u64,
+}
+
+impl StatementLine {
+    fn new(transaction: &CachedTransaction) -> Self {
+        let outgoing = !transaction.incoming;
+
+        return Self {
+            timestamp: transaction.timestamp,
+            hash: transaction.hash.clone(),
+            direction: if outgoing { "outgoing" } else { "incoming" },
+            counterparty: transaction.counterparty.map(|address| address.to_string()).unwrap_or_default(),
+            amount: transaction.amount.unwrap_or(0),
+            coin_type: transaction.coin_type.clone(),
+            // Fees are paid by the sender
+            gas_fee: if outgoing { transaction.gas_fee } else { 0 },
+            status: if transaction.success { "success" } else { "failed" },
+            version: transaction.version,
+            sequence_number: transaction.sequence_number,
+        };
+    }
+
+    fn to_csv(&self) -> String {
+        return [
+            self.timestamp.to_string(),
+            self.hash.clone(),
+            self.direction.to_string(),
+            self.counterparty.clone(),
+            self.amount.to_string(),
+            self.coin_type.clone(),
+            self.gas_fee.to_string(),
+            self.status.to_string(),
+            self.version.to_string(),
+            self.sequence_number.to_string(),
+        ].iter().map(|field| csv_field(field)).collect::<Vec<String>>().join(",");
+    }
+}
+
+/// Writes the statement of the account to the file, returns the number of transactions written.
+/// The transactions are the ones sent by the account and the transfers it received.
+pub fn export_transactions(
+    transactions: &[CachedTransaction],
+    format: ExportFormat,
+    path: &Path,
+) -> Result<usize, AptosError> {
+    let lines: Vec<StatementLine> = transactions.iter()
+        .map(StatementLine::new)
+        .collect();
+
+    let bytes = match format {
+        ExportFormat::Csv => {
+            let mut rows: Vec<String> = vec![CSV_HEADER.to_string()];
+            rows.extend(lines.iter().map(|line| line.to_csv()));
+
+            (rows.join("\n") + "\n").into_bytes()
+        },
+        ExportFormat::Json => match serde_json::to_vec_pretty(&lines) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::StorageFailed)
+            },
+        },
+    };
+
+    return match std::fs::write(path, bytes) {
+        Ok(()) => Ok(lines.len()),
+        Err(error) => {
+            log::error!("{}", error);
+            Err(AptosError::StorageFailed)
+        },
+    };
+}
+
+/// Quotes the field when it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    return match value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(incoming: bool) -> CachedTransaction {
+        return CachedTransaction {
+            type_transaction: "user_transaction".to_string(),
+            hash: "0x01".to_string(),
+            sequence_number: 3,
+            version: 42,
+            timestamp: 1660000000,
+            sender: AccountAddress::parse("0xa").unwrap(),
+            incoming: incoming,
+            counterparty: Some(AccountAddress::parse("0xb").unwrap()),
+            function: "0x1::coin::transfer".to_string(),
+            amount: Some(100),
+            coin_type: "0x1::coin::Coin<0x1::a::A, 0x1::b::B>".to_string(),
+            gas_fee: 20,
+            success: true,
+            vm_status: String::new(),
+        };
+    }
+
+    #[test]
+    fn incoming_transfers_have_no_fee() {
+        let outgoing = StatementLine::new(&transfer(false));
+        let incoming = StatementLine::new(&transfer(true));
+
+        assert_eq!((outgoing.direction, outgoing.gas_fee), ("outgoing", 20));
+        assert_eq!((incoming.direction, incoming.gas_fee), ("incoming", 0));
+        assert_eq!(incoming.counterparty, AccountAddress::parse("0xb").unwrap().to_string());
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        let row = StatementLine::new(&transfer(false)).to_csv();
+
+        assert!(row.contains(",\"0x1::coin::Coin<0x1::a::A, 0x1::b::B>\","));
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+        assert_eq!(csv_field("plain"), "plain");
+    }
+}
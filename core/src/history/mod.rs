@@ -0,0 +1,465 @@
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+use crate::storage::Storage;
+
+const TRANSACTIONS_KEY: &str = "transactions";
+// Transfers AptosCoin without a type argument
+const APTOS_ACCOUNT_TRANSFER: &str = "0x1::aptos_account::transfer";
+
+/// Committed transaction sent by an account or transferring coins to it, as kept in the history cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedTransaction {
+    /// Type given by the node, `user_transaction` for the committed transactions
+    #[serde(rename = "type", default = "user_transaction")]
+    pub type_transaction: String,
+    pub hash: String,
+    pub sequence_number: u64,
+    pub version: u64,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    pub sender: AccountAddress,
+    /// True for the transfers received by the account, found from its received events
+    #[serde(default)]
+    pub incoming: bool,
+    /// Recipient of an outgoing transfer, sender of an incoming one
+    pub counterparty: Option<AccountAddress>,
+    /// `address::module::function` of the payload, empty for other payloads
+    pub function: String,
+    /// Amount of a coin transfer
+    pub amount: Option<u64>,
+    /// Coin of the transfer, empty for other transactions
+    pub coin_type: String,
+    /// Gas used multiplied by the gas unit price
+    pub gas_fee: u64,
+    pub success: bool,
+    pub vm_status: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionStatusFilter {
+    Any,
+    Success,
+    Failure,
+}
+
+/// Criteria of a history query, every criterion is optional
+pub struct HistoryFilter {
+    /// Unix timestamps in seconds, inclusive
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+    pub counterparty: Option<AccountAddress>,
+    pub status: TransactionStatusFilter,
+    /// Ledger versions, inclusive
+    pub start_version: Option<u64>,
+    pub end_version: Option<u64>,
+    pub coin_type: Option<String>,
+}
+
+/// Transactions of an account on a network, ordered by version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AccountHistory {
+    network: String,
+    address: AccountAddress,
+    transactions: Vec<CachedTransaction>,
+    /// Sequence number of the next received event to fetch
+    #[serde(default)]
+    next_received_event: u64,
+}
+
+/// Transactions fetched from the node, kept in the storage per account and network.
+/// A sync only fetches the transactions after the last cached sequence number and the received events after
+/// the last cached one, reads never hit the network.
+pub struct TransactionHistory {
+    storage: Arc<Storage>,
+    network: String,
+    /// Serializes the syncs so two of them never append the same transactions
+    sync_lock: futures::lock::Mutex<()>,
+    histories: Mutex<Option<Vec<AccountHistory>>>,
+}
+
+impl TransactionHistory {
+    pub fn new(storage: Arc<Storage>, network: String) -> Self {
+        return Self {
+            storage: storage,
+            network: network,
+            sync_lock: futures::lock::Mutex::new(()),
+            histories: Mutex::new(None),
+        };
+    }
+
+    /// Drops the loaded histories, they are read again from the storage on the next access
+    pub fn reload(&self) {
+        *self.histories.lock().unwrap() = None;
+    }
+
+    /// Fetches the new transactions of the account and the transfers it received, returns how many were added
+    pub async fn sync(&self, rest_client: &AptosRestClient, address: &AccountAddress) -> Result<usize, AptosError> {
+        let _sync_guard = self.sync_lock.lock().await;
+
+        let (next_sequence_number, next_received_event) = self.cursors(address);
+
+        let mut new_transactions = match sent_transactions(rest_client, address, next_sequence_number).await {
+            Ok(transactions) => transactions,
+            Err(error) => return Err(error),
+        };
+
+        let (received_transactions, next_received_event_after) = match received_transfers(rest_client, address, next_received_event).await {
+            Ok(received) => received,
+            Err(error) => return Err(error),
+        };
+
+        new_transactions.extend(received_transactions);
+
+        let added = new_transactions.len();
+
+        // Nothing is written when there is nothing new
+        if added > 0 || next_received_event_after != next_received_event {
+            let result = self.update(|histories| {
+                let history = account_history(histories, &self.network, address);
+
+                history.transactions.extend(new_transactions);
+                history.transactions.sort_by_key(|t| (t.version, t.incoming));
+                history.transactions.dedup_by(|a, b| a.hash == b.hash && a.incoming == b.incoming);
+                history.next_received_event = std::cmp::max(history.next_received_event, next_received_event_after);
+            });
+
+            if let Err(error) = result {
+                return Err(error);
+            }
+        }
+
+        return Ok(added);
+    }
+
+    /// Returns the cached transactions of the account matching the filter, most recent first
+    pub fn query(&self, address: &AccountAddress, filter: &HistoryFilter) -> Vec<CachedTransaction> {
+        let mut transactions: Vec<CachedTransaction> = self.transactions(address)
+            .into_iter()
+            .filter(|t| filter.start_time.map_or(true, |start_time| t.timestamp >= start_time))
+            .filter(|t| filter.end_time.map_or(true, |end_time| t.timestamp <= end_time))
+            .filter(|t| filter.start_version.map_or(true, |start_version| t.version >= start_version))
+            .filter(|t| filter.end_version.map_or(true, |end_version| t.version <= end_version))
+            .filter(|t| filter.counterparty.map_or(true, |counterparty| t.counterparty == Some(counterparty)))
+            .filter(|t| filter.coin_type.as_ref().map_or(true, |coin_type| t.coin_type == *coin_type))
+            .filter(|t| match filter.status {
+                TransactionStatusFilter::Any => true,
+                TransactionStatusFilter::Success => t.success,
+                TransactionStatusFilter::Failure => !t.success,
+            })
+            .collect();
+
+        transactions.reverse();
+
+        return transactions;
+    }
+
+    /// Returns the cached transactions of the account, ordered by sequence number
+    pub fn transactions(&self, address: &AccountAddress) -> Vec<CachedTransaction> {
+        let mut histories = self.histories.lock().unwrap();
+
+        return load(&mut histories, &self.storage)
+            .iter()
+            .find(|history| history.network == self.network && history.address == *address)
+            .map(|history| history.transactions.clone())
+            .unwrap_or_default();
+    }
+
+    /// Returns the next sequence number and the next received event to fetch
+    fn cursors(&self, address: &AccountAddress) -> (u64, u64) {
+        let mut histories = self.histories.lock().unwrap();
+
+        let history = load(&mut histories, &self.storage)
+            .iter()
+            .find(|history| history.network == self.network && history.address == *address);
+
+        return match history {
+            Some(history) => (
+                history.transactions.iter()
+                    .filter(|t| !t.incoming)
+                    .map(|t| t.sequence_number + 1)
+                    .max()
+                    .unwrap_or(0),
+                history.next_received_event,
+            ),
+            None => (0, 0),
+        };
+    }
+
+    fn update<F: FnOnce(&mut Vec<AccountHistory>)>(&self, change: F) -> Result<(), AptosError> {
+        let mut histories = self.histories.lock().unwrap();
+
+        let loaded = load(&mut histories, &self.storage);
+        change(loaded);
+
+        return self.storage.set(TRANSACTIONS_KEY, loaded);
+    }
+}
+
+fn load<'a>(histories: &'a mut Option<Vec<AccountHistory>>, storage: &Storage) -> &'a mut Vec<AccountHistory> {
+    return histories.get_or_insert_with(|| storage.get(TRANSACTIONS_KEY).unwrap_or_default());
+}
+
+fn account_history<'a>(histories: &'a mut Vec<AccountHistory>, network: &str, address: &AccountAddress) -> &'a mut AccountHistory {
+    let position = match histories.iter().position(|h| h.network == network && h.address == *address) {
+        Some(position) => position,
+        None => {
+            histories.push(AccountHistory {
+                network: network.to_string(),
+                address: *address,
+                transactions: Vec::new(),
+                next_received_event: 0,
+            });
+
+            histories.len() - 1
+        },
+    };
+
+    return &mut histories[position];
+}
+
+fn user_transaction() -> String {
+    return "user_transaction".to_string();
+}
+
+/// Transactions sent by the account from the sequence number, in order
+async fn sent_transactions(rest_client: &AptosRestClient, address: &AccountAddress, start: u64) -> Result<Vec<CachedTransaction>, AptosError> {
+    let mut start = start;
+    let mut sent_transactions: Vec<CachedTransaction> = Vec::new();
+
+    loop {
+        let transactions = match rest_client.get_account_transactions_page(address, Some(start), Some(TRANSACTIONS_PAGE_LIMIT)).await {
+            Ok(transactions) => transactions,
+            Err(error) => return Err(error),
+        };
+
+        let count = transactions.len();
+
+        for transaction in &transactions {
+            match cached_transaction(transaction) {
+                Some(cached) => sent_transactions.push(cached),
+                None => log::warn!("Transaction {} not cached", transaction.hash),
+            }
+        }
+
+        if count < TRANSACTIONS_PAGE_LIMIT as usize {
+            break;
+        }
+
+        start += count as u64;
+    }
+
+    return Ok(sent_transactions);
+}
+
+/// Transfers received by the account from the received event sequence number, with the sequence number of the next event.
+/// The transaction of each event is fetched by its version for its hash and sender.
+async fn received_transfers(rest_client: &AptosRestClient, address: &AccountAddress, start: u64) -> Result<(Vec<CachedTransaction>, u64), AptosError> {
+    let mut next_event = start;
+    let mut received_transfers: Vec<CachedTransaction> = Vec::new();
+
+    loop {
+        let events = match rest_client.get_received_events(address, next_event, TRANSACTIONS_PAGE_LIMIT).await {
+            Ok(events) => events,
+            Err(error) => return Err(error),
+        };
+
+        for event in &events {
+            let version = match event.version.as_ref().and_then(|version| version.parse::<u64>().ok()) {
+                Some(version) => version,
+                None => {
+                    log::warn!("Received event {} of {} has no version, not cached", event.sequence_number, address);
+                    continue
+                },
+            };
+
+            let transaction = match rest_client.get_transaction_by_version(version).await {
+                Ok(transaction) => transaction,
+                Err(error) => return Err(error),
+            };
+
+            match received_transfer(&transaction, event) {
+                Some(cached) => received_transfers.push(cached),
+                None => log::warn!("Transaction {} not cached", transaction.hash),
+            }
+        }
+
+        next_event = match events.last().and_then(|event| event.sequence_number.parse::<u64>().ok()) {
+            Some(sequence_number) => std::cmp::max(next_event, sequence_number + 1),
+            None => next_event,
+        };
+
+        if events.len() < TRANSACTIONS_PAGE_LIMIT as usize {
+            break;
+        }
+    }
+
+    return Ok((received_transfers, next_event));
+}
+
+/// Transfer received by the account, the amount is the one of the event and the counterparty the sender
+fn received_transfer(transaction: &model::Transaction, event: &model::AccountEvent) -> Option<CachedTransaction> {
+    let amount = match event.data["amount"].as_str().and_then(|amount| amount.parse::<u64>().ok()) {
+        Some(amount) => amount,
+        None => return None,
+    };
+
+    return cached_transaction(transaction).map(|cached| CachedTransaction {
+        incoming: true,
+        counterparty: Some(cached.sender),
+        amount: Some(amount),
+        coin_type: aptos_coin_type().to_string(),
+        ..cached
+    });
+}
+
+/// Only committed user transactions are cached
+fn cached_transaction(transaction: &model::Transaction) -> Option<CachedTransaction> {
+    let sender = match transaction.sender.as_ref().map(|sender| AccountAddress::parse(sender)) {
+        Some(Ok(sender)) => sender,
+        _ => return None,
+    };
+
+    let version = match transaction.version.as_ref().map(|version| version.parse::<u64>()) {
+        Some(Ok(version)) => version,
+        _ => return None,
+    };
+
+    let sequence_number = match transaction.sequence_number.parse::<u64>() {
+        Ok(sequence_number) => sequence_number,
+        Err(_) => return None,
+    };
+
+    let timestamp = transaction.timestamp.as_ref()
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .map(|timestamp_us| timestamp_us / 1_000_000)
+        .unwrap_or(0);
+
+    let payload = transaction.payload.clone().unwrap_or_default();
+
+    let function = payload["function"].as_str().unwrap_or("").to_string();
+    let arguments = payload["arguments"].as_array().cloned().unwrap_or_default();
+
+    // Coin transfers take the recipient then the amount
+    let is_transfer = function.ends_with("::transfer") || function.ends_with("::transfer_coins");
+
+    let (counterparty, amount) = match is_transfer {
+        true => (
+            arguments.first()
+                .and_then(|argument| argument.as_str())
+                .and_then(|argument| AccountAddress::parse(argument).ok()),
+            arguments.get(1)
+                .and_then(|argument| argument.as_str())
+                .and_then(|argument| argument.parse::<u64>().ok()),
+        ),
+        false => (None, None),
+    };
+
+    let coin_type = match function.as_str() {
+        APTOS_ACCOUNT_TRANSFER => APTOS_COIN_TYPE,
+        _ if is_transfer => payload["type_arguments"][0].as_str().unwrap_or(""),
+        _ => "",
+    };
+
+    // Stored in the printed form so they compare whatever the address form
+    let coin_type = match TypeTag::validate(coin_type) {
+        Ok(type_tag) => type_tag.to_string(),
+        Err(_) => coin_type.to_string(),
+    };
+
+    let gas_used = transaction.gas_used.as_ref().and_then(|gas| gas.parse::<u64>().ok()).unwrap_or(0);
+    let gas_unit_price = transaction.gas_unit_price.as_ref().and_then(|price| price.parse::<u64>().ok()).unwrap_or(0);
+
+    return Some(CachedTransaction {
+        type_transaction: transaction.type_transaction.clone(),
+        hash: transaction.hash.clone(),
+        sequence_number: sequence_number,
+        version: version,
+        timestamp: timestamp,
+        sender: sender,
+        incoming: false,
+        counterparty: counterparty,
+        function: function,
+        amount: amount,
+        coin_type: coin_type,
+        gas_fee: gas_used.saturating_mul(gas_unit_price),
+        success: transaction.success.unwrap_or(false),
+        vm_status: transaction.vm_status.clone().unwrap_or_default(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: &str = "0xa";
+    const RECIPIENT: &str = "0xb";
+
+    fn transaction(function: &str, type_arguments: serde_json::Value, arguments: serde_json::Value) -> model::Transaction {
+        return serde_json::from_value(serde_json::json!({
+            "type": "user_transaction",
+            "hash": "0x01",
+            "sequence_number": "3",
+            "success": true,
+            "vm_status": "Executed successfully",
+            "version": "42",
+            "timestamp": "1660000000000000",
+            "sender": SENDER,
+            "payload": {
+                "type": "entry_function_payload",
+                "function": function,
+                "type_arguments": type_arguments,
+                "arguments": arguments,
+            },
+            "gas_used": "10",
+            "gas_unit_price": "2",
+        })).unwrap();
+    }
+
+    #[test]
+    fn outgoing_transfer_counterparty_is_the_recipient() {
+        let cached = cached_transaction(&transaction("0x1::coin::transfer", serde_json::json!([APTOS_COIN_TYPE]), serde_json::json!([RECIPIENT, "100"]))).unwrap();
+
+        assert!(!cached.incoming);
+        assert_eq!(cached.type_transaction, "user_transaction");
+        assert_eq!(cached.counterparty, Some(AccountAddress::parse(RECIPIENT).unwrap()));
+        assert_eq!(cached.amount, Some(100));
+        assert_eq!(cached.coin_type, aptos_coin_type().to_string());
+        assert_eq!(cached.timestamp, 1660000000);
+        assert_eq!(cached.gas_fee, 20);
+    }
+
+    #[test]
+    fn other_functions_have_no_counterparty() {
+        let cached = cached_transaction(&transaction("0x1::message::set_message", serde_json::json!([]), serde_json::json!([RECIPIENT]))).unwrap();
+
+        assert_eq!(cached.counterparty, None);
+        assert_eq!(cached.amount, None);
+        assert_eq!(cached.coin_type, "");
+    }
+
+    #[test]
+    fn aptos_account_transfers_send_aptos_coin() {
+        let cached = cached_transaction(&transaction(APTOS_ACCOUNT_TRANSFER, serde_json::json!([]), serde_json::json!([RECIPIENT, "100"]))).unwrap();
+
+        assert_eq!(cached.coin_type, aptos_coin_type().to_string());
+    }
+
+    #[test]
+    fn incoming_transfer_counterparty_is_the_sender() {
+        let event: model::AccountEvent = serde_json::from_value(serde_json::json!({
+            "sequence_number": "0",
+            "type": "0x1::coin::DepositEvent",
+            "data": {"amount": "75"},
+            "version": "42",
+        })).unwrap();
+
+        let received = received_transfer(&transaction(APTOS_ACCOUNT_TRANSFER, serde_json::json!([]), serde_json::json!([RECIPIENT, "75"])), &event).unwrap();
+
+        assert!(received.incoming);
+        assert_eq!(received.counterparty, Some(AccountAddress::parse(SENDER).unwrap()));
+        assert_eq!(received.amount, Some(75));
+        assert_eq!(received.coin_type, aptos_coin_type().to_string());
+    }
+}
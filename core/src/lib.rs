@@ -5,6 +5,14 @@ mod rust_data;
 mod core;
 mod aptos;
 mod logger;
+mod events;
+mod watcher;
+mod registry;
+mod storage;
+mod history;
+mod export;
+mod contacts;
+mod sequence;
 
 mod core_proto { include!(concat!(env!("OUT_DIR"), "/core_proto.rs")); }
 
@@ -35,6 +43,7 @@ pub extern "C" fn create_core(
     log_level: *const std::os::raw::c_char,
     aptos_rest_url: *const std::os::raw::c_char,
     aptos_faucet_url: *const std::os::raw::c_char,
+    event_callback: async_dispatch::RustCallback,
 ) -> *const core::Core {
     let log_filter: LevelFilter = match raw_char_to_str(log_level) {
         "debug" => LevelFilter::Debug,
@@ -50,8 +59,11 @@ pub extern "C" fn create_core(
     let core = Core::new(
         raw_char_to_string(aptos_rest_url),
         raw_char_to_string(aptos_faucet_url),
+        event_callback,
     );
 
+    logger::set_event_emitter(core.event_emitter.clone());
+
     let core_arc = Arc::new(core);
 
     return Arc::into_raw(core_arc);
@@ -62,7 +74,26 @@ pub extern "C" fn create_core(
 pub unsafe extern "C" fn free_core(core: *mut core::Core) {
     assert!(!core.is_null());
 
-    Box::from_raw(core);
+    let core_arc = Arc::from_raw(core);
+
+    // Requests still running may outlive this call, they must not reach the host callback anymore
+    logger::clear_event_emitter(&core_arc.event_emitter);
+    core_arc.event_emitter.close();
+
+    // The background tasks hold references to the core, they are stopped before it is released
+    core_arc.stop();
+    drop(core_arc);
+}
+
+/// Register the host callback used to sign with keys that stay on the host side
+#[no_mangle]
+pub unsafe extern "C" fn set_signer_callback(
+    core: *const core::Core,
+    signer_callback: aptos::RustSignerCallback,
+) {
+    assert!(!core.is_null());
+
+    *(*core).signer_callback.write().unwrap() = Some(signer_callback);
 }
 
 /// Call a synchronous request
@@ -1,5 +1,27 @@
+use std::sync::{Arc, RwLock};
 use log::{Record, Level, Metadata};
 
+use crate::core_proto::*;
+use crate::events::EventEmitter;
+
+lazy_static::lazy_static! {
+    static ref EVENT_EMITTER: RwLock<Option<Arc<EventEmitter>>> = RwLock::new(None);
+}
+
+/// Forwards the log lines to the host as `LogEvent` when the `LOG` topic is subscribed
+pub fn set_event_emitter(event_emitter: Arc<EventEmitter>) {
+    *EVENT_EMITTER.write().unwrap() = Some(event_emitter);
+}
+
+/// Stops forwarding the log lines to the given emitter, called when its core is freed
+pub fn clear_event_emitter(event_emitter: &Arc<EventEmitter>) {
+    let mut current = EVENT_EMITTER.write().unwrap();
+
+    if current.as_ref().map_or(false, |current| Arc::ptr_eq(current, event_emitter)) {
+        *current = None;
+    }
+}
+
 pub struct Logger;
 
 impl log::Log for Logger {
@@ -15,7 +37,14 @@ impl log::Log for Logger {
             Level::Debug => println!("🛠 DEBUG - {}", record.args()),
             Level::Trace => println!("🔮 TRACE - {}", record.args()),
         }
+
+        if let Some(event_emitter) = EVENT_EMITTER.read().unwrap().as_ref() {
+            event_emitter.emit(event::Kind::Log(LogEvent {
+                level: record.level().to_string(),
+                message: record.args().to_string(),
+            }));
+        }
     }
 
     fn flush(&self) {}
-}
\ No newline at end of file
+}
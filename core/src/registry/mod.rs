@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::aptos::*;
+use crate::storage::{now_secs, Storage};
+
+const ACCOUNTS_KEY: &str = "accounts";
+
+/// Account or shared wallet known by the core
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisteredAccount {
+    pub address: AccountAddress,
+    /// Current signing keys
+    pub account_signer: AccountSigner,
+    pub label: String,
+    /// REST URL of the node the account was registered with
+    pub network: String,
+    /// Unix timestamps in seconds
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// Registered accounts indexed by their address and by their current keys
+#[derive(Default)]
+struct Accounts {
+    by_address: HashMap<AccountAddress, RegisteredAccount>,
+    by_signer: HashMap<AccountSigner, AccountAddress>,
+}
+
+impl Accounts {
+    fn insert(&mut self, account: RegisteredAccount) {
+        if let Some(previous) = self.by_address.get(&account.address) {
+            if self.by_signer.get(&previous.account_signer) == Some(&account.address) {
+                self.by_signer.remove(&previous.account_signer);
+            }
+        }
+
+        self.by_signer.insert(account.account_signer.clone(), account.address);
+        self.by_address.insert(account.address, account);
+    }
+
+    /// Ordered by creation date
+    fn sorted(&self) -> Vec<RegisteredAccount> {
+        let mut accounts: Vec<RegisteredAccount> = self.by_address.values().cloned().collect();
+
+        accounts.sort_by_key(|account| (account.created_at, account.address));
+
+        return accounts;
+    }
+
+    fn remove(&mut self, address: &AccountAddress) -> Option<RegisteredAccount> {
+        let account = match self.by_address.remove(address) {
+            Some(account) => account,
+            None => return None,
+        };
+
+        if self.by_signer.get(&account.account_signer) == Some(address) {
+            self.by_signer.remove(&account.account_signer);
+        }
+
+        return Some(account);
+    }
+}
+
+/// Accounts and shared wallets known by the core with their current signing keys.
+/// The address of an account never changes, its keys do when the authentication key is rotated.
+/// Every change is written to the storage, unless the stored accounts could not be read.
+pub struct AccountRegistry {
+    accounts: RwLock<Accounts>,
+    storage: Arc<Storage>,
+    network: String,
+    /// False when the stored accounts are invalid, they are kept as is until they are loaded
+    writable: AtomicBool,
+}
+
+impl AccountRegistry {
+    pub fn new(storage: Arc<Storage>, network: String) -> Self {
+        return Self {
+            accounts: RwLock::new(Accounts::default()),
+            storage: storage,
+            network: network,
+            writable: AtomicBool::new(true),
+        };
+    }
+
+    /// Reads the stored accounts once the storage is opened, accounts registered before are kept.
+    /// Nothing is written when the stored accounts are invalid.
+    pub fn load(&self) -> Result<usize, AptosError> {
+        let mut accounts = self.accounts.write().unwrap();
+
+        let stored_accounts: Vec<RegisteredAccount> = match self.storage.read(ACCOUNTS_KEY) {
+            Ok(stored_accounts) => stored_accounts.unwrap_or_default(),
+            Err(error) => {
+                self.writable.store(false, Ordering::SeqCst);
+                return Err(error)
+            },
+        };
+
+        self.writable.store(true, Ordering::SeqCst);
+
+        for account in stored_accounts {
+            if !accounts.by_address.contains_key(&account.address) {
+                accounts.insert(account);
+            }
+        }
+
+        return match self.save(&accounts) {
+            Ok(()) => Ok(accounts.by_address.len()),
+            Err(error) => Err(error),
+        };
+    }
+
+    /// Registers a new account, the label and creation date of a known one are kept
+    pub fn register(&self, address: AccountAddress, account_signer: AccountSigner) -> Result<(), AptosError> {
+        let now = now_secs();
+        let mut accounts = self.accounts.write().unwrap();
+
+        let account = match accounts.by_address.get(&address) {
+            Some(account) => RegisteredAccount {
+                account_signer: account_signer,
+                updated_at: now,
+                ..account.clone()
+            },
+            None => RegisteredAccount {
+                address: address,
+                account_signer: account_signer,
+                label: String::new(),
+                network: self.network.clone(),
+                created_at: now,
+                updated_at: now,
+            },
+        };
+
+        accounts.insert(account);
+
+        return self.save(&accounts);
+    }
+
+    /// Returns the current keys of the account
+    pub fn get(&self, address: &AccountAddress) -> Option<AccountSigner> {
+        return self.accounts.read().unwrap().by_address.get(address).map(|account| account.account_signer.clone());
+    }
+
+    pub fn account(&self, address: &AccountAddress) -> Option<RegisteredAccount> {
+        return self.accounts.read().unwrap().by_address.get(address).cloned();
+    }
+
+    /// Returns the accounts ordered by creation date
+    pub fn accounts(&self) -> Vec<RegisteredAccount> {
+        return self.accounts.read().unwrap().sorted();
+    }
+
+    /// Returns the address of the account currently using these keys
+    pub fn address_of(&self, account_signer: &AccountSigner) -> Option<AccountAddress> {
+        return self.accounts.read().unwrap().by_signer.get(account_signer).cloned();
+    }
+
+    /// Replaces the keys of the account after a rotation, the address stays the same
+    pub fn rotate(&self, address: &AccountAddress, account_signer: AccountSigner) -> Result<(), AptosError> {
+        return self.register(*address, account_signer);
+    }
+
+    pub fn set_label(&self, address: &AccountAddress, label: &str) -> Result<(), AptosError> {
+        let mut accounts = self.accounts.write().unwrap();
+
+        match accounts.by_address.get_mut(address) {
+            Some(account) => {
+                account.label = label.to_string();
+                account.updated_at = now_secs();
+            },
+            None => return Err(AptosError::UnknownAccount),
+        };
+
+        return self.save(&accounts);
+    }
+
+    /// Returns false when the account is not registered
+    pub fn remove(&self, address: &AccountAddress) -> Result<bool, AptosError> {
+        let mut accounts = self.accounts.write().unwrap();
+
+        return match accounts.remove(address).is_some() {
+            true => self.save(&accounts).map(|()| true),
+            false => Ok(false),
+        };
+    }
+
+    /// Called with the lock held, so that the last write has the last changes
+    fn save(&self, accounts: &Accounts) -> Result<(), AptosError> {
+        if !self.writable.load(Ordering::SeqCst) {
+            log::error!("Accounts not saved, the stored ones are invalid");
+            return Err(AptosError::StorageFailed);
+        }
+
+        return self.storage.set(ACCOUNTS_KEY, &accounts.sorted());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> AccountRegistry {
+        return AccountRegistry::new(Arc::new(Storage::new()), "http://localhost".to_string());
+    }
+
+    #[test]
+    fn address_of_follows_rotations() {
+        let registry = registry();
+        let address = AccountAddress::parse("0xa").unwrap();
+        let old_keys = AccountSigner::single("aa");
+        let new_keys = AccountSigner::single("bb");
+
+        registry.register(address, old_keys.clone()).unwrap();
+        assert_eq!(registry.address_of(&old_keys), Some(address));
+
+        registry.rotate(&address, new_keys.clone()).unwrap();
+        assert_eq!(registry.address_of(&old_keys), None);
+        assert_eq!(registry.address_of(&new_keys), Some(address));
+
+        registry.remove(&address).unwrap();
+        assert_eq!(registry.address_of(&new_keys), None);
+    }
+
+    #[test]
+    fn invalid_stored_accounts_are_not_overwritten() {
+        let registry = registry();
+        registry.storage.set(ACCOUNTS_KEY, &serde_json::json!([{"address": "0x0x1"}])).unwrap();
+
+        assert!(matches!(registry.load(), Err(AptosError::StorageFailed)));
+        assert!(matches!(
+            registry.register(AccountAddress::parse("0xa").unwrap(), AccountSigner::single("aa")),
+            Err(AptosError::StorageFailed)
+        ));
+
+        let stored: serde_json::Value = registry.storage.get(ACCOUNTS_KEY).unwrap();
+        assert_eq!(stored, serde_json::json!([{"address": "0x0x1"}]));
+    }
+}
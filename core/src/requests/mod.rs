@@ -1,10 +1,19 @@
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::core::Core;
 use crate::core_proto::*;
 use crate::aptos::*;
+use crate::events;
+use crate::registry;
+use crate::history::{CachedTransaction, HistoryFilter, TransactionStatusFilter};
+use crate::export;
+use crate::contacts;
+use crate::sequence;
 
-const BITMAP_NUM_OF_BYTES: usize = 4;
+// Gas limit of module publishing unless given
+const PUBLISH_MAX_GAS_AMOUNT: u64 = 100_000;
 
 pub fn handle_get_backtrace(_req: GetBacktraceRequest) -> Box<GetBacktraceResponse> {
     let bt = backtrace::Backtrace::new();
@@ -17,152 +26,2717 @@ pub fn handle_get_backtrace(_req: GetBacktraceRequest) -> Box<GetBacktraceRespon
     return Box::new(response);
 }
 
-pub fn handle_create_account(_req: CreateAccountRequest) -> Box<CreateAccountResponse> {
+pub fn handle_create_account(core: Arc<Core>, req: CreateAccountRequest) -> Box<CreateAccountResponse> {
     let account = AptosAccount::new(None);
 
-    let response = CreateAccountResponse {
-        keypair: account.keypair(),
-        public_key: account.public_key(),
-        ..Default::default()
+    let response = match create_account(&core, &req, &account) {
+        Ok(keypair) => CreateAccountResponse {
+            keypair: keypair,
+            public_key: account.public_key(),
+            address: account.address().to_string(),
+            ..Default::default()
+        },
+        Err(error) => CreateAccountResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
     };
 
     return Box::new(response);
 }
 
-pub fn handle_create_wallet(req: CreateWalletRequest) -> Box<CreateWalletResponse> {
+/// Stores the keypair first, the account is only registered once its key is safe.
+/// Returns the keypair unless it is in the keystore.
+fn create_account(core: &Core, req: &CreateAccountRequest, account: &AptosAccount) -> Result<String, AptosError> {
+    let keypair = match req.keystore_path.is_empty() {
+        true => account.keypair(),
+        false => match Keystore::open(Path::new(&req.keystore_path), &req.keystore_password).and_then(|mut keystore| keystore.insert(account)) {
+            Ok(()) => String::new(),
+            Err(error) => return Err(error),
+        },
+    };
+
+    return core.account_registry.register(account.address(), AccountSigner::single(&account.public_key()))
+        .and_then(|()| core.account_registry.set_label(&account.address(), &req.label))
+        .map(|()| keypair);
+}
+
+/// Returns the error of a failed request as sent to the host
+fn core_error(error: AptosError) -> CoreError {
+    let kind = match error {
+        AptosError::InvalidJSON => ErrorKind::InvalidJson,
+        AptosError::InvalidRequest => ErrorKind::InvalidRequest,
+        AptosError::InvalidResponse => ErrorKind::InvalidResponse,
+        AptosError::InvalidKeypair => ErrorKind::InvalidKeypair,
+        AptosError::UnknownSigner => ErrorKind::UnknownSigner,
+        AptosError::NotEnoughSignatures => ErrorKind::NotEnoughSignatures,
+        AptosError::InvalidKeystore => ErrorKind::InvalidKeystore,
+        AptosError::SigningFailed => ErrorKind::SigningFailed,
+        AptosError::InvalidBundle => ErrorKind::InvalidBundle,
+        AptosError::BundleMismatch => ErrorKind::BundleMismatch,
+        AptosError::InvalidTransaction => ErrorKind::InvalidTransaction,
+        AptosError::UnsupportedPayload => ErrorKind::UnsupportedPayload,
+        AptosError::InvalidAddress => ErrorKind::InvalidAddress,
+        AptosError::InvalidSignature => ErrorKind::InvalidSignature,
+        AptosError::UnknownAccount => ErrorKind::UnknownAccount,
+        AptosError::StorageFailed => ErrorKind::StorageFailed,
+        AptosError::UnknownContact => ErrorKind::UnknownContact,
+        AptosError::ContactExists => ErrorKind::ContactExists,
+        AptosError::InvalidTypeTag => ErrorKind::InvalidTypeTag,
+        AptosError::InvalidArgument => ErrorKind::InvalidArgument,
+        AptosError::InvalidFunction => ErrorKind::InvalidFunction,
+        AptosError::RecipientNotRegistered => ErrorKind::RecipientNotRegistered,
+        AptosError::UnknownTransaction => ErrorKind::UnknownTransaction,
+        AptosError::InvalidGasPrice => ErrorKind::InvalidGasPrice,
+        AptosError::InvalidThreshold => ErrorKind::InvalidThreshold,
+        AptosError::TooManyPublicKeys => ErrorKind::TooManyPublicKeys,
+        AptosError::InvalidPassword => ErrorKind::InvalidPassword,
+        AptosError::InvalidChainId => ErrorKind::InvalidChainId,
+        AptosError::NotFound => ErrorKind::NotFound,
+        AptosError::InvalidSequenceNumber => ErrorKind::InvalidSequenceNumber,
+        AptosError::TimeWentBackwards => ErrorKind::TimeWentBackwards,
+        AptosError::TransactionTimeout => ErrorKind::TransactionTimeout,
+    };
+
+    return CoreError {
+        kind: kind as i32,
+        message: format!("{:?}", error),
+    };
+}
+
+/// Returns the shared wallet of the keys, all of them sign when the threshold is 0
+fn shared_wallet(public_keys: &[String], threshold: u32) -> Result<AptosSharedWallet, AptosError> {
     let mut wallet = AptosSharedWallet::new();
 
-    for public_key in req.public_keys {
-        wallet.add_public_key(public_key);
+    if public_keys.is_empty() {
+        log::error!("A shared wallet needs at least one public key");
+        return Err(AptosError::InvalidKeypair);
     }
 
-    let response = CreateWalletResponse {
-        address: wallet.address(),
-        ..Default::default()
+    for public_key in public_keys {
+        if let Err(error) = wallet.add_public_key(public_key.clone()) {
+            return Err(error);
+        }
+    }
+
+    if threshold > 0 {
+        if let Err(error) = wallet.set_threshold(threshold) {
+            return Err(error);
+        }
+    }
+
+    return Ok(wallet);
+}
+
+pub fn handle_create_wallet(core: Arc<Core>, req: CreateWalletRequest) -> Box<CreateWalletResponse> {
+    let response = match create_wallet(&core, &req) {
+        Ok(address) => CreateWalletResponse {
+            address: address.to_string(),
+            ..Default::default()
+        },
+        Err(error) => CreateWalletResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
     };
 
     return Box::new(response);
 }
 
-pub async fn handle_fund_wallet(core: Arc<Core>, req: FundWalletRequest) -> Box<FundWalletResponse> {
-    let transactions = core.aptos_faucet_client.fund_account(&req.address, req.amount)
-        .await
-        .unwrap();
+fn create_wallet(core: &Core, req: &CreateWalletRequest) -> Result<AccountAddress, AptosError> {
+    let wallet = match shared_wallet(&req.public_keys, req.threshold) {
+        Ok(wallet) => wallet,
+        Err(error) => return Err(error),
+    };
 
-    let response = FundWalletResponse {
-        transactions: transactions.iter().map(|hash| Transaction {
-            type_transaction: String::from("pending_transaction"),
-            hash: hash.to_string(),
-        }).collect(),
+    return core.account_registry.register(wallet.address(), AccountSigner::multi(&wallet))
+        .and_then(|()| core.account_registry.set_label(&wallet.address(), &req.label))
+        .map(|()| wallet.address());
+}
+
+pub fn handle_validate_address(req: ValidateAddressRequest) -> Box<ValidateAddressResponse> {
+    let response = match AccountAddress::validate(&req.address) {
+        Ok(address) => ValidateAddressResponse {
+            valid: true,
+            address: address.to_string(),
+            short_address: address.to_short_string(),
+            ..Default::default()
+        },
+        Err(reason) => ValidateAddressResponse {
+            valid: false,
+            error: reason.to_string(),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn account_keys(account_signer: &AccountSigner) -> AccountKeys {
+    return match account_signer {
+        AccountSigner::Single { public_key } => AccountKeys {
+            kind: AccountKind::Ed25519 as i32,
+            public_keys: vec![public_key.clone()],
+            ..Default::default()
+        },
+        AccountSigner::Multi { public_keys, threshold } => AccountKeys {
+            kind: AccountKind::MultiEd25519 as i32,
+            public_keys: public_keys.clone(),
+            threshold: *threshold as u32,
+        },
+    };
+}
+
+fn registered_account(account: &registry::RegisteredAccount) -> RegisteredAccount {
+    return RegisteredAccount {
+        address: account.address.to_string(),
+        keys: Some(account_keys(&account.account_signer)),
+        label: account.label.clone(),
+        network: account.network.clone(),
+        created_at: account.created_at,
+        updated_at: account.updated_at,
+    };
+}
+
+pub fn handle_open_storage(core: Arc<Core>, req: OpenStorageRequest) -> Box<OpenStorageResponse> {
+    let response = match open_storage(&core, req) {
+        Ok(response) => response,
+        Err(error) => OpenStorageResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn open_storage(core: &Arc<Core>, req: OpenStorageRequest) -> Result<OpenStorageResponse, AptosError> {
+    let schema_version = match core.storage.open(Path::new(&req.path)) {
+        Ok(schema_version) => schema_version,
+        Err(error) => return Err(error),
+    };
+
+    let accounts_count = match core.account_registry.load() {
+        Ok(accounts_count) => accounts_count,
+        Err(error) => return Err(error),
+    };
+
+    core.transaction_history.reload();
+
+    if let Err(error) = core.address_book.load() {
+        return Err(error);
+    }
+
+    return Ok(OpenStorageResponse {
+        schema_version: schema_version,
+        accounts_count: accounts_count as u32,
+        ..Default::default()
+    });
+}
+
+pub fn handle_list_accounts(core: Arc<Core>, _req: ListAccountsRequest) -> Box<ListAccountsResponse> {
+    let response = ListAccountsResponse {
+        accounts: core.account_registry.accounts().iter().map(registered_account).collect(),
         ..Default::default()
     };
 
     return Box::new(response);
 }
 
-pub async fn handle_get_wallet_balance(core: Arc<Core>, req: GetWalletBalanceRequest) -> Box<GetWalletBalanceResponse> {
-    let balance = core.aptos_rest_client.get_account_resource(&req.address, "0x1::TestCoin::Balance")
-        .await
-        .unwrap()["data"]["coin"]["value"]
-        .as_str()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap();
+pub fn handle_get_registered_account(core: Arc<Core>, req: GetRegisteredAccountRequest) -> Box<GetRegisteredAccountResponse> {
+    let response = match AccountAddress::parse(&req.address) {
+        Ok(address) => GetRegisteredAccountResponse {
+            account: core.account_registry.account(&address).as_ref().map(registered_account),
+            ..Default::default()
+        },
+        Err(error) => GetRegisteredAccountResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+/// Registers an account created elsewhere, its address is derived from the keys when not given
+pub fn handle_register_account(core: Arc<Core>, req: RegisterAccountRequest) -> Box<RegisterAccountResponse> {
+    let response = match register_account(&core, req) {
+        Ok(account) => RegisterAccountResponse {
+            account: Some(registered_account(&account)),
+            ..Default::default()
+        },
+        Err(error) => RegisterAccountResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn register_account(core: &Arc<Core>, req: RegisterAccountRequest) -> Result<registry::RegisteredAccount, AptosError> {
+    let account_signer = match account_keys_signer(&req.keys.clone().unwrap_or_default()) {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let address = match req.address.is_empty() {
+        true => account_signer.authentication_key(),
+        false => AccountAddress::parse(&req.address),
+    };
 
-    let response = GetWalletBalanceResponse {
-        balance: balance,
+    let address = match address {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let registered = core.account_registry.register(address, account_signer)
+        .and_then(|()| core.account_registry.set_label(&address, &req.label));
+
+    if let Err(error) = registered {
+        return Err(error);
+    }
+
+    return core.account_registry.account(&address).ok_or(AptosError::UnknownAccount);
+}
+
+pub fn handle_update_account(core: Arc<Core>, req: UpdateAccountRequest) -> Box<UpdateAccountResponse> {
+    let updated = AccountAddress::parse(&req.address)
+        .and_then(|address| core.account_registry.set_label(&address, &req.label).map(|()| address))
+        .and_then(|address| core.account_registry.account(&address).ok_or(AptosError::UnknownAccount));
+
+    let response = match updated {
+        Ok(account) => UpdateAccountResponse {
+            account: Some(registered_account(&account)),
+            ..Default::default()
+        },
+        Err(error) => UpdateAccountResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+pub fn handle_delete_account(core: Arc<Core>, req: DeleteAccountRequest) -> Box<DeleteAccountResponse> {
+    let deleted = AccountAddress::parse(&req.address)
+        .and_then(|address| core.account_registry.remove(&address));
+
+    let response = match deleted {
+        Ok(deleted) => DeleteAccountResponse {
+            deleted: deleted,
+            ..Default::default()
+        },
+        Err(error) => DeleteAccountResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn contact(contact: &contacts::Contact) -> Contact {
+    return Contact {
+        label: contact.label.clone(),
+        address: contact.address.to_string(),
+        notes: contact.notes.clone(),
+        network: contact.network.clone(),
+        created_at: contact.created_at,
+        updated_at: contact.updated_at,
+    };
+}
+
+pub fn handle_add_contact(core: Arc<Core>, req: AddContactRequest) -> Box<AddContactResponse> {
+    let added_contact = AccountAddress::parse(&req.address)
+        .and_then(|address| core.address_book.add(&req.label, address, &req.notes, &req.network));
+
+    let response = match added_contact {
+        Ok(added_contact) => AddContactResponse {
+            contact: Some(contact(&added_contact)),
+            ..Default::default()
+        },
+        Err(error) => AddContactResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+pub fn handle_update_contact(core: Arc<Core>, req: UpdateContactRequest) -> Box<UpdateContactResponse> {
+    let response = match update_contact(&core, &req) {
+        Ok(updated_contact) => UpdateContactResponse {
+            contact: Some(contact(&updated_contact)),
+            ..Default::default()
+        },
+        Err(error) => UpdateContactResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn update_contact(core: &Arc<Core>, req: &UpdateContactRequest) -> Result<contacts::Contact, AptosError> {
+    let address = match &req.address {
+        Some(address) => match AccountAddress::parse(address) {
+            Ok(address) => Some(address),
+            Err(error) => return Err(error),
+        },
+        None => None,
+    };
+
+    // A contact always has a label
+    let new_label = req.new_label.as_deref().filter(|label| !label.is_empty());
+
+    return core.address_book.update(&req.label, new_label, address, req.notes.as_deref());
+}
+
+pub fn handle_delete_contact(core: Arc<Core>, req: DeleteContactRequest) -> Box<DeleteContactResponse> {
+    let response = match core.address_book.remove(&req.label) {
+        Ok(deleted) => DeleteContactResponse {
+            deleted: deleted,
+            ..Default::default()
+        },
+        Err(error) => DeleteContactResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+pub fn handle_find_contacts(core: Arc<Core>, req: FindContactsRequest) -> Box<FindContactsResponse> {
+    let response = FindContactsResponse {
+        contacts: core.address_book.find(&req.prefix).iter().map(contact).collect(),
         ..Default::default()
     };
 
     return Box::new(response);
 }
 
-pub async fn handle_create_wallet_transaction(core: Arc<Core>, req: CreateWalletTransactionRequest) -> Box<CreateWalletTransactionResponse> {
-    let payload = serde_json::json!({
-        "type": "script_function_payload",
-        "function": "0x1::TestCoin::transfer",
-        "type_arguments": [],
-        "arguments": [format!("0x{}", req.address_to), req.amount.to_string()],
-    });
+/// Returns the name shown for an address: its contact, else the label of the registered account
+fn address_label(core: &Core, address: &AccountAddress) -> String {
+    return core.address_book.label_of(address)
+        .or_else(|| core.account_registry.account(address).map(|account| account.label))
+        .unwrap_or_default();
+}
+
+pub fn handle_validate_type_tag(req: ValidateTypeTagRequest) -> Box<ValidateTypeTagResponse> {
+    let response = match TypeTag::validate(&req.type_tag) {
+        Ok(type_tag) => ValidateTypeTagResponse {
+            valid: true,
+            type_tag: type_tag.to_string(),
+            ..Default::default()
+        },
+        Err(reason) => ValidateTypeTagResponse {
+            valid: false,
+            error: reason,
+            ..Default::default()
+        },
+    };
 
-    let transaction = core.aptos_rest_client.generate_transaction(&req.address_from, payload)
-        .await
-        .unwrap();
+    return Box::new(response);
+}
 
-    let response = CreateWalletTransactionResponse {
-        transaction: transaction,
+pub fn handle_subscribe(core: Arc<Core>, req: SubscribeRequest) -> Box<SubscribeResponse> {
+    let topics = core.event_emitter.subscribe(&events::topics_from_i32(&req.topics));
+
+    let response = SubscribeResponse {
+        topics: events::topics_to_i32(&topics),
         ..Default::default()
     };
 
     return Box::new(response);
 }
 
-pub async fn handle_sign_wallet_transaction(core: Arc<Core>, req: SignWalletTransactionRequest) -> Box<SignWalletTransactionResponse> {
-    let mut account_from = AptosAccount::new(Some(req.keypair));
-    
-    let signature = core.aptos_rest_client.sign_transaction(&mut account_from, req.transaction)
-        .await
-        .unwrap();
+pub fn handle_unsubscribe(core: Arc<Core>, req: UnsubscribeRequest) -> Box<UnsubscribeResponse> {
+    let topics = core.event_emitter.unsubscribe(&events::topics_from_i32(&req.topics));
 
-    let response = SignWalletTransactionResponse {
-        signature: signature,
+    let response = UnsubscribeResponse {
+        topics: events::topics_to_i32(&topics),
         ..Default::default()
     };
 
     return Box::new(response);
 }
 
-fn bitmap_set_bit(input: &mut [u8; BITMAP_NUM_OF_BYTES], index: usize) {
-    let bucket = index / 8;
-    // It's always invoked with index < 32, thus there is no need to check range.
-    let bucket_pos = index - (bucket * 8);
-    input[bucket] |= 128 >> bucket_pos as u8;
+pub fn handle_watch_address(core: Arc<Core>, req: WatchAddressRequest) -> Box<WatchAddressResponse> {
+    let response = match AccountAddress::parse(&req.address) {
+        Ok(address) => WatchAddressResponse {
+            addresses: core.account_watcher.watch(address).iter().map(|address| address.to_string()).collect(),
+            ..Default::default()
+        },
+        Err(error) => WatchAddressResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+pub fn handle_unwatch_address(core: Arc<Core>, req: UnwatchAddressRequest) -> Box<UnwatchAddressResponse> {
+    let response = match AccountAddress::parse(&req.address) {
+        Ok(address) => UnwatchAddressResponse {
+            addresses: core.account_watcher.unwatch(&address).iter().map(|address| address.to_string()).collect(),
+            ..Default::default()
+        },
+        Err(error) => UnwatchAddressResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
 }
 
-pub async fn handle_submit_wallet_transaction(core: Arc<Core>, req: SubmitWalletTransactionRequest) -> Box<SubmitWalletTransactionResponse> {
-    let public_keys: Vec<String> = req.signed_payloads.iter().map(|s| s.public_key.clone()).collect();
-    let signatures: Vec<String> = req.signed_payloads.iter().map(|s| s.signature.clone()).collect();
+pub fn handle_set_watcher_interval(core: Arc<Core>, req: SetWatcherIntervalRequest) -> Box<SetWatcherIntervalResponse> {
+    let interval = core.account_watcher.set_interval(Duration::from_millis(req.interval_ms));
+
+    let response = SetWatcherIntervalResponse {
+        interval_ms: interval.as_millis() as u64,
+        ..Default::default()
+    };
+
+    return Box::new(response);
+}
 
-    let mut bitmap = [0u8; BITMAP_NUM_OF_BYTES];
+fn read_transaction_bundle(source: Option<TransactionBundleSource>) -> Result<TransactionBundle, AptosError> {
+    use crate::core_proto::transaction_bundle_source::Source;
 
-    for i in 0..signatures.len() {
-        bitmap_set_bit(&mut bitmap, i);
+    return match source.and_then(|s| s.source) {
+        Some(Source::Data(data)) => TransactionBundle::from_bytes(&data),
+        Some(Source::Base64(encoded)) => TransactionBundle::from_base64(&encoded),
+        Some(Source::Path(path)) => TransactionBundle::read_file(Path::new(&path)),
+        None => Err(AptosError::InvalidBundle),
+    };
+}
+
+fn write_transaction_bundle(bundle: &TransactionBundle, output_path: &str) -> Result<TransactionBundleOutput, AptosError> {
+    if !output_path.is_empty() {
+        if let Err(error) = bundle.write_file(Path::new(output_path)) {
+            return Err(error);
+        }
     }
 
-    let signature_payload: serde_json::Value = serde_json::json!({
-        "type": "multi_ed25519_signature",
-        "public_keys": serde_json::json!(public_keys),
-        "signatures": serde_json::json!(signatures),
-        "threshold": signatures.len(),
-        "bitmap": format!("0x{}", hex::encode(bitmap)),
+    return Ok(TransactionBundleOutput {
+        data: bundle.to_bytes(),
+        base64: bundle.to_base64(),
     });
+}
 
-    let transaction = core.aptos_rest_client.submit_transaction(req.transaction, signature_payload)
-        .await
-        .unwrap();
+pub async fn handle_create_transaction_bundle(core: Arc<Core>, req: CreateTransactionBundleRequest) -> Box<CreateTransactionBundleResponse> {
+    let response = match create_transaction_bundle(&core, req).await {
+        Ok(bundle) => CreateTransactionBundleResponse {
+            bundle: Some(bundle),
+            ..Default::default()
+        },
+        Err(error) => CreateTransactionBundleResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
 
-    let response = SubmitWalletTransactionResponse {
-        transaction: Some(Transaction {
-            type_transaction: transaction.type_transaction.clone(),
-            hash: transaction.hash.clone(),
+    return Box::new(response);
+}
+
+/// The types of the arguments are taken from the ABI of the called module, the cosigners sign the arguments encoded with them
+async fn create_transaction_bundle(core: &Core, req: CreateTransactionBundleRequest) -> Result<TransactionBundleOutput, AptosError> {
+    let wallet = match shared_wallet(&req.public_keys, req.threshold) {
+        Ok(wallet) => wallet,
+        Err(error) => return Err(error),
+    };
+
+    let chain_id = match chain_id(req.chain_id) {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &req.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    return TransactionBundle::new(req.transaction.clone(), &wallet, chain_id, &parameter_types)
+        .and_then(|bundle| write_transaction_bundle(&bundle, &req.output_path));
+}
+
+/// Chain ids are a single byte, 0 isn't a chain
+fn chain_id(chain_id: u32) -> Result<u8, AptosError> {
+    return match chain_id {
+        1..=255 => Ok(chain_id as u8),
+        _ => {
+            log::error!("Invalid chain id {}", chain_id);
+            Err(AptosError::InvalidChainId)
+        },
+    };
+}
+
+pub fn handle_add_bundle_signature(req: AddBundleSignatureRequest) -> Box<AddBundleSignatureResponse> {
+    let response = match add_bundle_signature(req) {
+        Ok(bundle) => AddBundleSignatureResponse {
+            bundle: Some(bundle),
+            ..Default::default()
+        },
+        Err(error) => AddBundleSignatureResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn add_bundle_signature(req: AddBundleSignatureRequest) -> Result<TransactionBundleOutput, AptosError> {
+    let mut bundle = match read_transaction_bundle(req.bundle) {
+        Ok(bundle) => bundle,
+        Err(error) => return Err(error),
+    };
+
+    let signed_payload = req.signed_payload.unwrap_or_default();
+
+    if let Err(error) = bundle.add_signature(&signed_payload.public_key, &signed_payload.signature) {
+        return Err(error);
+    }
+
+    return write_transaction_bundle(&bundle, &req.output_path);
+}
+
+pub fn handle_merge_transaction_bundles(req: MergeTransactionBundlesRequest) -> Box<MergeTransactionBundlesResponse> {
+    let response = match merge_transaction_bundles(req) {
+        Ok(bundle) => MergeTransactionBundlesResponse {
+            bundle: Some(bundle),
+            ..Default::default()
+        },
+        Err(error) => MergeTransactionBundlesResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn merge_transaction_bundles(req: MergeTransactionBundlesRequest) -> Result<TransactionBundleOutput, AptosError> {
+    let mut bundle: Option<TransactionBundle> = None;
+
+    for source in req.bundles {
+        let other = match read_transaction_bundle(Some(source)) {
+            Ok(other) => other,
+            Err(error) => return Err(error),
+        };
+
+        match bundle.as_mut() {
+            Some(bundle) => if let Err(error) = bundle.merge(&other) {
+                return Err(error);
+            },
+            None => bundle = Some(other),
+        };
+    }
+
+    return match bundle {
+        Some(bundle) => write_transaction_bundle(&bundle, &req.output_path),
+        None => {
+            log::error!("No bundle to merge");
+            Err(AptosError::InvalidBundle)
+        },
+    };
+}
+
+pub fn handle_inspect_transaction_bundle(req: InspectTransactionBundleRequest) -> Box<InspectTransactionBundleResponse> {
+    let bundle = match read_transaction_bundle(req.bundle) {
+        Ok(bundle) => bundle,
+        Err(error) => {
+            let response = InspectTransactionBundleResponse {
+                error: Some(core_error(error)),
+                ..Default::default()
+            };
+
+            return Box::new(response);
+        },
+    };
+
+    // Read bundles are validated, their wallet is valid
+    let address = bundle.wallet()
+        .map(|wallet| wallet.address().to_string())
+        .unwrap_or_default();
+
+    let response = InspectTransactionBundleResponse {
+        version: bundle.version,
+        transaction: bundle.transaction.clone(),
+        address: address,
+        public_keys: bundle.public_keys.clone(),
+        threshold: bundle.threshold as u32,
+        signed_payloads: bundle.signatures.iter().map(|s| crate::core_proto::SignedPayload {
+            public_key: s.public_key.clone(),
+            signature: s.signature.clone(),
+        }).collect(),
+        complete: bundle.is_complete(),
+        chain_id: bundle.chain_id as u32,
+        parameter_types: bundle.parameter_types.clone(),
+        ..Default::default()
+    };
+
+    return Box::new(response);
+}
+
+/// The address, when set, must be valid and the chain id, when set, must fit the one of the transactions
+fn sign_message_payload(message: Option<MessageToSign>) -> Result<SignMessagePayload, AptosError> {
+    let message = message.unwrap_or_default();
+
+    if !message.address.is_empty() {
+        if let Err(error) = AccountAddress::parse(&message.address) {
+            return Err(error);
+        }
+    }
+
+    let chain_id = match message.chain_id {
+        0 => None,
+        _ => match chain_id(message.chain_id) {
+            Ok(chain_id) => Some(chain_id),
+            Err(error) => return Err(error),
+        },
+    };
+
+    return Ok(SignMessagePayload {
+        address: Some(message.address).filter(|a| !a.is_empty()),
+        application: Some(message.application).filter(|a| !a.is_empty()),
+        chain_id: chain_id,
+        message: message.message,
+        nonce: message.nonce,
+    });
+}
+
+pub fn handle_sign_message(core: Arc<Core>, req: SignMessageRequest) -> Box<SignMessageResponse> {
+    let response = match sign_message(&core, req) {
+        Ok(response) => response,
+        Err(error) => SignMessageResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn sign_message(core: &Arc<Core>, req: SignMessageRequest) -> Result<SignMessageResponse, AptosError> {
+    let signer = match signer(core, req.signer(), &req.keypair, &req.public_key, &req.keystore_path, &req.keystore_password) {
+        Ok(signer) => signer,
+        Err(error) => return Err(error),
+    };
+
+    let full_message = match sign_message_payload(req.message) {
+        Ok(payload) => payload.full_message(),
+        Err(error) => return Err(error),
+    };
+
+    let signature = match signer.sign(full_message.as_bytes()) {
+        Ok(signature) => signature,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(SignMessageResponse {
+        full_message: full_message,
+        prefix: MESSAGE_PREFIX.to_string(),
+        signed_payload: Some(crate::core_proto::SignedPayload {
+            public_key: signer.public_key(),
+            signature: signature,
         }),
         ..Default::default()
+    });
+}
+
+pub fn handle_verify_message(core: Arc<Core>, req: VerifyMessageRequest) -> Box<VerifyMessageResponse> {
+    let response = match verify_message(&core, req) {
+        Ok(response) => response,
+        Err(error) => VerifyMessageResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
     };
 
     return Box::new(response);
 }
 
-pub async fn handle_get_wallet_transactions(core: Arc<Core>, req: GetWalletTransactionsRequest)  -> Box<GetWalletTransactionsResponse> {
-    let transactions = core.aptos_rest_client.get_account_transactions(&req.address)
-        .await
-        .unwrap();
+fn verify_message(core: &Arc<Core>, req: VerifyMessageRequest) -> Result<VerifyMessageResponse, AptosError> {
+    let account_signer = match account_signer(req.kind(), &req.public_keys, req.threshold, &req.signed_payloads) {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
 
-    let response = GetWalletTransactionsResponse {
-        transactions: transactions.iter().map(|t| Transaction {
-            type_transaction: t.type_transaction.clone(),
-            hash: t.hash.clone(),
-        }).collect(),
+    let signed_payloads = signed_payloads(&req.signed_payloads);
+
+    let payload = match sign_message_payload(req.message) {
+        Ok(payload) => payload,
+        Err(error) => return Err(error),
+    };
+
+    let full_message = payload.full_message();
+
+    // Malformed signatures are reported in the checks
+    let verifications = account_signer.verify(full_message.as_bytes(), &signed_payloads);
+
+    let valid_signatures = verifications.iter()
+        .filter(|v| v.status == SignatureStatus::Valid)
+        .map(|v| v.public_key.clone())
+        .collect::<std::collections::HashSet<String>>()
+        .len();
+
+    // The signed address must be the one of the keys, or use them since a rotation
+    let address_matches = match payload.address.as_ref().and_then(|address| AccountAddress::parse(address).ok()) {
+        Some(address) => Some(address) == account_signer.authentication_key().ok()
+            || core.account_registry.get(&address).as_ref() == Some(&account_signer),
+        None => true,
+    };
+
+    return Ok(VerifyMessageResponse {
+        full_message: full_message,
+        checks: signature_checks(&verifications),
+        verified: address_matches && valid_signatures >= account_signer.threshold(),
         ..Default::default()
+    });
+}
+
+pub async fn handle_fund_wallet(core: Arc<Core>, req: FundWalletRequest) -> Box<FundWalletResponse> {
+    let response = match fund_wallet(&core, &req).await {
+        Ok(transactions) => FundWalletResponse {
+            transactions: transactions.iter().map(|hash| Transaction {
+                type_transaction: String::from("pending_transaction"),
+                hash: hash.to_string(),
+            }).collect(),
+            ..Default::default()
+        },
+        Err(error) => FundWalletResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn fund_wallet(core: &Core, req: &FundWalletRequest) -> Result<Vec<String>, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    return core.aptos_faucet_client.fund_account(&address, req.amount).await;
+}
+
+pub async fn handle_get_wallet_balance(core: Arc<Core>, req: GetWalletBalanceRequest) -> Box<GetWalletBalanceResponse> {
+    let response = match get_wallet_balance(&core, &req).await {
+        Ok(balance) => GetWalletBalanceResponse {
+            balance: balance,
+            ..Default::default()
+        },
+        Err(error) => GetWalletBalanceResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
     };
 
     return Box::new(response);
-}
\ No newline at end of file
+}
+
+async fn get_wallet_balance(core: &Core, req: &GetWalletBalanceRequest) -> Result<u64, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    return core.aptos_rest_client.get_account_balance(&address).await;
+}
+
+/// Call of `0x1::aptos_account::transfer`, sends AptosCoin and creates the recipient account when missing
+fn transfer_payload(address_to: &AccountAddress, amount: u64) -> serde_json::Value {
+    let arguments = vec![MoveValue::Address(*address_to), MoveValue::U64(amount)];
+
+    return EntryFunction::new("0x1::aptos_account::transfer", Vec::new(), arguments)
+        .unwrap()
+        .to_payload();
+}
+
+fn move_value(argument: &MoveArgument) -> Result<MoveValue, AptosError> {
+    let narrow = |value: u32, max: u32| match value <= max {
+        true => Ok(value),
+        false => {
+            log::error!("Argument {} out of range", value);
+            Err(AptosError::InvalidArgument)
+        },
+    };
+
+    return match &argument.value {
+        Some(move_argument::Value::Bool(value)) => Ok(MoveValue::Bool(*value)),
+        Some(move_argument::Value::U8(value)) => narrow(*value, u8::MAX as u32).map(|value| MoveValue::U8(value as u8)),
+        Some(move_argument::Value::U16(value)) => narrow(*value, u16::MAX as u32).map(|value| MoveValue::U16(value as u16)),
+        Some(move_argument::Value::U32(value)) => Ok(MoveValue::U32(*value)),
+        Some(move_argument::Value::U64(value)) => Ok(MoveValue::U64(*value)),
+        Some(move_argument::Value::U128(value)) => match value.trim().parse::<u128>() {
+            Ok(value) => Ok(MoveValue::U128(value)),
+            Err(error) => {
+                log::error!("Invalid u128 {}: {}", value, error);
+                Err(AptosError::InvalidArgument)
+            },
+        },
+        Some(move_argument::Value::U256(value)) => MoveValue::u256(value),
+        Some(move_argument::Value::Address(address)) => AccountAddress::parse(address).map(MoveValue::Address),
+        Some(move_argument::Value::String(value)) => Ok(MoveValue::String(value.clone())),
+        Some(move_argument::Value::Bytes(value)) => Ok(MoveValue::Bytes(value.clone())),
+        Some(move_argument::Value::Vector(vector)) => vector.values.iter()
+            .map(move_value)
+            .collect::<Result<Vec<MoveValue>, AptosError>>()
+            .map(MoveValue::Vector),
+        Some(move_argument::Value::Option(option)) => match &option.value {
+            Some(value) => move_value(value).map(|value| MoveValue::Option(Some(Box::new(value)))),
+            None => Ok(MoveValue::Option(None)),
+        },
+        None => {
+            log::error!("Argument without value");
+            Err(AptosError::InvalidArgument)
+        },
+    };
+}
+
+/// Parses the call, the error tells which part of it is invalid
+fn entry_function(entry_function_call: &EntryFunctionCall) -> Result<EntryFunction, EntryFunctionError> {
+    let mut type_arguments: Vec<TypeTag> = Vec::new();
+
+    for (index, type_argument) in entry_function_call.type_arguments.iter().enumerate() {
+        match TypeTag::parse(type_argument) {
+            Ok(type_tag) => type_arguments.push(type_tag),
+            Err(_) => return Err(call_error(EntryFunctionErrorKind::InvalidTypeArgument, format!("type argument {} is invalid", index), index)),
+        }
+    }
+
+    let mut arguments: Vec<MoveValue> = Vec::new();
+
+    for (index, argument) in entry_function_call.arguments.iter().enumerate() {
+        match move_value(argument) {
+            Ok(value) => arguments.push(value),
+            Err(_) => return Err(call_error(EntryFunctionErrorKind::InvalidArgument, format!("argument {} is invalid", index), index)),
+        }
+    }
+
+    return match EntryFunction::new(&entry_function_call.function, type_arguments, arguments) {
+        Ok(entry_function) => Ok(entry_function),
+        Err(_) => Err(call_error(EntryFunctionErrorKind::InvalidFunction, format!("invalid function {}", entry_function_call.function), 0)),
+    };
+}
+
+fn call_error(kind: EntryFunctionErrorKind, message: String, argument_index: usize) -> EntryFunctionError {
+    return EntryFunctionError {
+        kind: kind as i32,
+        message: message,
+        argument_index: argument_index as u32,
+    };
+}
+
+fn entry_function_error(error: &AbiMismatch) -> EntryFunctionError {
+    let (kind, argument_index) = match error {
+        AbiMismatch::ModuleNotFound => (EntryFunctionErrorKind::ModuleNotFound, 0),
+        AbiMismatch::FunctionNotFound => (EntryFunctionErrorKind::FunctionNotFound, 0),
+        AbiMismatch::NotAnEntryFunction => (EntryFunctionErrorKind::NotAnEntryFunction, 0),
+        AbiMismatch::TypeArgumentCount { .. } => (EntryFunctionErrorKind::TypeArgumentCount, 0),
+        AbiMismatch::ArgumentCount { .. } => (EntryFunctionErrorKind::ArgumentCount, 0),
+        AbiMismatch::ArgumentType { index, .. } => (EntryFunctionErrorKind::ArgumentType, *index),
+    };
+
+    return call_error(kind, error.to_string(), argument_index);
+}
+
+/// Why a wallet transaction wasn't built
+enum CreateTransactionError {
+    /// The call is invalid or doesn't match the ABI of its module
+    EntryFunction(EntryFunctionError),
+    Core(AptosError),
+}
+
+pub async fn handle_create_wallet_transaction(core: Arc<Core>, req: CreateWalletTransactionRequest) -> Box<CreateWalletTransactionResponse> {
+    let response = match create_wallet_transaction(&core, &req).await {
+        Ok(transaction) => CreateWalletTransactionResponse {
+            transaction: transaction,
+            ..Default::default()
+        },
+        Err(CreateTransactionError::EntryFunction(error)) => CreateWalletTransactionResponse {
+            entry_function_error: Some(error),
+            ..Default::default()
+        },
+        Err(CreateTransactionError::Core(error)) => CreateWalletTransactionResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn create_wallet_transaction(core: &Core, req: &CreateWalletTransactionRequest) -> Result<String, CreateTransactionError> {
+    let address_from = match AccountAddress::parse(&req.address_from) {
+        Ok(address) => address,
+        Err(error) => return Err(CreateTransactionError::Core(error)),
+    };
+
+    let payload = match &req.entry_function {
+        Some(entry_function_call) => {
+            let entry_function = match entry_function(entry_function_call) {
+                Ok(entry_function) => entry_function,
+                Err(error) => {
+                    log::error!("Invalid call of {}: {}", entry_function_call.function, error.message);
+                    return Err(CreateTransactionError::EntryFunction(error))
+                },
+            };
+
+            if !req.skip_validation {
+                let module = match core.module_abis.module(&core.aptos_rest_client, &entry_function.module_address, &entry_function.module_name).await {
+                    Ok(module) => module,
+                    Err(error) => return Err(CreateTransactionError::Core(error)),
+                };
+
+                // Calls that would abort on chain are not built
+                if let Err(error) = validate_entry_function(module.as_ref(), &entry_function) {
+                    log::error!("Invalid call of {}: {}", entry_function.function_id(), error);
+                    return Err(CreateTransactionError::EntryFunction(entry_function_error(&error)));
+                }
+            }
+
+            entry_function.to_payload()
+        },
+        None => match AccountAddress::parse(&req.address_to) {
+            Ok(address_to) => transfer_payload(&address_to, req.amount),
+            Err(error) => return Err(CreateTransactionError::Core(error)),
+        },
+    };
+
+    return match generate_transaction(core, &address_from, payload, DEFAULT_MAX_GAS_AMOUNT).await {
+        Ok(transaction) => Ok(transaction),
+        Err(error) => Err(CreateTransactionError::Core(error)),
+    };
+}
+
+/// Returns the signer holding the key described by the request
+fn signer(core: &Core, kind: SignerKind, keypair: &str, public_key: &str, keystore_path: &str, keystore_password: &str) -> Result<Box<dyn Signer>, AptosError> {
+    return match kind {
+        SignerKind::InMemory => match AptosAccount::from_keypair(keypair) {
+            Ok(account) => Ok(Box::new(account)),
+            Err(error) => Err(error),
+        },
+        SignerKind::Keystore => Ok(Box::new(KeystoreSigner::new(Path::new(keystore_path), keystore_password, public_key))),
+        SignerKind::Host => match *core.signer_callback.read().unwrap() {
+            Some(signer_callback) => Ok(Box::new(FfiSigner::new(signer_callback, public_key))),
+            None => {
+                log::error!("No signer callback registered by the host");
+                Err(AptosError::SigningFailed)
+            },
+        },
+    };
+}
+
+pub async fn handle_sign_wallet_transaction(core: Arc<Core>, req: SignWalletTransactionRequest) -> Box<SignWalletTransactionResponse> {
+    let response = match sign_wallet_transaction(&core, req).await {
+        Ok(response) => response,
+        Err(error) => SignWalletTransactionResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn sign_wallet_transaction(core: &Core, req: SignWalletTransactionRequest) -> Result<SignWalletTransactionResponse, AptosError> {
+    let signer = match signer(core, req.signer(), &req.keypair, &req.public_key, &req.keystore_path, &req.keystore_password) {
+        Ok(signer) => signer,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &req.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    let signature = match core.aptos_rest_client.sign_transaction(signer.as_ref(), req.transaction, &parameter_types).await {
+        Ok(signature) => signature,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(SignWalletTransactionResponse {
+        signature: signature,
+        public_key: signer.public_key(),
+        ..Default::default()
+    });
+}
+
+/// Chooses the authenticator from the kind of account the transaction is sent from
+fn account_signer(kind: AccountKind, public_keys: &[String], threshold: u32, signed_payloads: &[crate::core_proto::SignedPayload]) -> Result<AccountSigner, AptosError> {
+    let public_keys: Vec<String> = match public_keys.is_empty() {
+        true => signed_payloads.iter().map(|s| s.public_key.clone()).collect(),
+        false => public_keys.to_vec(),
+    };
+
+    return match kind {
+        AccountKind::Ed25519 => match public_keys.first() {
+            Some(public_key) => Ok(AccountSigner::single(public_key)),
+            None => {
+                log::error!("No public key given");
+                Err(AptosError::InvalidKeypair)
+            },
+        },
+        AccountKind::MultiEd25519 => shared_wallet(&public_keys, threshold).map(|wallet| AccountSigner::multi(&wallet)),
+    };
+}
+
+fn signed_payloads(signed_payloads: &[crate::core_proto::SignedPayload]) -> Vec<crate::aptos::SignedPayload> {
+    return signed_payloads.iter().map(|s| crate::aptos::SignedPayload {
+        public_key: s.public_key.clone(),
+        signature: s.signature.clone(),
+    }).collect();
+}
+
+fn signature_checks(verifications: &[SignatureVerification]) -> Vec<SignatureCheck> {
+    return verifications.iter().map(|v| SignatureCheck {
+        public_key: v.public_key.clone(),
+        status: match v.status {
+            SignatureStatus::Valid => SignatureCheckStatus::SignatureValid,
+            SignatureStatus::Invalid => SignatureCheckStatus::SignatureInvalid,
+            SignatureStatus::Malformed => SignatureCheckStatus::SignatureMalformed,
+            SignatureStatus::UnknownSigner => SignatureCheckStatus::SignatureUnknownSigner,
+        } as i32,
+    }).collect();
+}
+
+pub async fn handle_verify_wallet_signatures(core: Arc<Core>, req: VerifyWalletSignaturesRequest) -> Box<VerifyWalletSignaturesResponse> {
+    let response = match verify_wallet_signatures(&core, &req).await {
+        Ok(response) => response,
+        Err(error) => VerifyWalletSignaturesResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn verify_wallet_signatures(core: &Arc<Core>, req: &VerifyWalletSignaturesRequest) -> Result<VerifyWalletSignaturesResponse, AptosError> {
+    let account_signer = match account_signer(req.kind(), &req.public_keys, req.threshold, &req.signed_payloads) {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &req.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    let signing_message = match signing_message(&req.transaction, chain_id, &parameter_types) {
+        Ok(signing_message) => signing_message,
+        Err(error) => return Err(error),
+    };
+
+    let verifications = account_signer.verify(&signing_message, &signed_payloads(&req.signed_payloads));
+
+    let valid_signatures = verifications.iter()
+        .filter(|v| v.status == SignatureStatus::Valid)
+        .map(|v| v.public_key.clone())
+        .collect::<std::collections::HashSet<String>>()
+        .len();
+
+    return Ok(VerifyWalletSignaturesResponse {
+        checks: signature_checks(&verifications),
+        valid_signatures: valid_signatures as u32,
+        threshold: account_signer.threshold() as u32,
+        ready: valid_signatures >= account_signer.threshold(),
+        ..Default::default()
+    });
+}
+
+pub async fn handle_submit_wallet_transaction(core: Arc<Core>, req: SubmitWalletTransactionRequest) -> Box<SubmitWalletTransactionResponse> {
+    let response = match submit_wallet_transaction(&core, req).await {
+        Ok(transaction) => SubmitWalletTransactionResponse {
+            transaction: Some(Transaction {
+                type_transaction: transaction.type_transaction.clone(),
+                hash: transaction.hash.clone(),
+            }),
+            ..Default::default()
+        },
+        Err(error) => SubmitWalletTransactionResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn submit_wallet_transaction(core: &Arc<Core>, req: SubmitWalletTransactionRequest) -> Result<model::Transaction, AptosError> {
+    let account_signer = match account_signer(req.kind(), &req.public_keys, req.threshold, &req.signed_payloads) {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let signed_payloads = signed_payloads(&req.signed_payloads);
+
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &req.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    let signing_message = match signing_message(&req.transaction, chain_id, &parameter_types) {
+        Ok(signing_message) => signing_message,
+        Err(error) => return Err(error),
+    };
+
+    // Invalid signatures are dropped, the submission fails if the threshold is not met
+    let signature_payload = match account_signer.verified_authenticator(&signing_message, &signed_payloads) {
+        Ok(signature_payload) => signature_payload,
+        Err(error) => return Err(error),
+    };
+
+    let transaction_hash = transaction_hash(&req.transaction, &signature_payload, chain_id, &parameter_types).ok();
+
+    let transaction = match submit_transaction(core, req.transaction, signature_payload, transaction_hash).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(error),
+    };
+
+    spawn_confirmation_watch(core.clone(), transaction.hash.clone());
+
+    return Ok(transaction);
+}
+
+/// Returns the hash the transaction will have once committed, to be recorded before submitting it
+pub async fn handle_get_transaction_hash(core: Arc<Core>, req: GetTransactionHashRequest) -> Box<GetTransactionHashResponse> {
+    let response = match get_transaction_hash(&core, &req).await {
+        Ok(hash) => GetTransactionHashResponse {
+            hash: hash,
+            ..Default::default()
+        },
+        Err(error) => GetTransactionHashResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn get_transaction_hash(core: &Core, req: &GetTransactionHashRequest) -> Result<String, AptosError> {
+    let account_signer = match account_signer(req.kind(), &req.public_keys, req.threshold, &req.signed_payloads) {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &req.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    let signing_message = match signing_message(&req.transaction, chain_id, &parameter_types) {
+        Ok(signing_message) => signing_message,
+        Err(error) => return Err(error),
+    };
+
+    // The hash of a transaction the node would reject is of no use
+    let signature_payload = match account_signer.verified_authenticator(&signing_message, &signed_payloads(&req.signed_payloads)) {
+        Ok(signature_payload) => signature_payload,
+        Err(error) => return Err(error),
+    };
+
+    return transaction_hash(&req.transaction, &signature_payload, chain_id, &parameter_types);
+}
+
+pub async fn handle_finalize_transaction_bundle(core: Arc<Core>, req: FinalizeTransactionBundleRequest) -> Box<FinalizeTransactionBundleResponse> {
+    let response = match finalize_transaction_bundle(&core, req).await {
+        Ok(transaction) => FinalizeTransactionBundleResponse {
+            transaction: Some(Transaction {
+                type_transaction: transaction.type_transaction.clone(),
+                hash: transaction.hash.clone(),
+            }),
+            ..Default::default()
+        },
+        Err(error) => FinalizeTransactionBundleResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn finalize_transaction_bundle(core: &Arc<Core>, req: FinalizeTransactionBundleRequest) -> Result<model::Transaction, AptosError> {
+    let bundle = match read_transaction_bundle(req.bundle) {
+        Ok(bundle) => bundle,
+        Err(error) => return Err(error),
+    };
+
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, &bundle.transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    // The cosigners signed the arguments encoded with the types of the bundle, they must be those of the ABI
+    match bundle.parameter_types() {
+        Ok(bundle_parameter_types) if bundle_parameter_types == parameter_types => (),
+        _ => {
+            log::error!("The argument types of the bundle are not those of the called function");
+            return Err(AptosError::BundleMismatch)
+        },
+    };
+
+    let signing_message = match signing_message(&bundle.transaction, chain_id, &parameter_types) {
+        Ok(signing_message) => signing_message,
+        Err(error) => return Err(error),
+    };
+
+    let signature_payload = match bundle.verified_authenticator(&signing_message) {
+        Ok(signature_payload) => signature_payload,
+        Err(error) => return Err(error),
+    };
+
+    let transaction_hash = transaction_hash(&bundle.transaction, &signature_payload, chain_id, &parameter_types).ok();
+
+    let transaction = match submit_transaction(core, bundle.transaction.clone(), signature_payload, transaction_hash).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(error),
+    };
+
+    spawn_confirmation_watch(core.clone(), transaction.hash.clone());
+
+    return Ok(transaction);
+}
+
+/// Generates the transaction with the next sequence number of the account, released when the generation fails
+async fn generate_transaction(
+    core: &Core,
+    address: &AccountAddress,
+    payload: serde_json::Value,
+    max_gas_amount: u64,
+) -> Result<String, AptosError> {
+    if let Err(error) = core.aptos_rest_client.ensure_clock_synced().await {
+        return Err(error);
+    }
+
+    let sequence_number = match core.sequence_numbers.allocate(address).await {
+        Ok(sequence_number) => sequence_number,
+        Err(error) => return Err(error),
+    };
+
+    let transaction = core.aptos_rest_client.generate_transaction_at(address, payload, sequence_number, max_gas_amount, DEFAULT_GAS_UNIT_PRICE);
+
+    match &transaction {
+        Ok(transaction) => core.sequence_numbers.generated(address, sequence_number, transaction),
+        Err(_) => core.sequence_numbers.release(address, sequence_number),
+    };
+
+    return transaction;
+}
+
+/// Hash of the signed transaction once committed, `None` when its payload can't be encoded locally
+async fn local_transaction_hash(core: &Core, transaction: &str, signature_payload: &serde_json::Value) -> Option<String> {
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(_) => return None,
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(_) => return None,
+    };
+
+    return transaction_hash(transaction, signature_payload, chain_id, &parameter_types).ok();
+}
+
+/// Submits the transaction, the sequence number of a rejected transaction is released.
+/// When the node can't be reached the transaction may have been received anyway: it is looked up by its
+/// locally computed hash and stays pending with this hash, so that submitting it again looks it up first.
+async fn submit_transaction(
+    core: &Core,
+    transaction: String,
+    signature_payload: serde_json::Value,
+    transaction_hash: Option<String>,
+) -> Result<model::Transaction, AptosError> {
+    let sequence_number = sequence::transaction_sequence_number(&transaction);
+
+    let submitted_before = match (&transaction_hash, &sequence_number) {
+        (Some(hash), Some((sender, sequence_number))) => core.sequence_numbers.pending(sender)
+            .iter()
+            .any(|pending| pending.sequence_number == *sequence_number && pending.hash.as_ref() == Some(hash)),
+        _ => false,
+    };
+
+    let known_transaction = match (&transaction_hash, submitted_before) {
+        (Some(hash), true) => core.aptos_rest_client.get_transaction(hash).await.ok(),
+        _ => None,
+    };
+
+    let result = match known_transaction {
+        Some(known_transaction) => {
+            log::info!("Transaction {} already submitted", known_transaction.hash);
+            Ok(known_transaction)
+        },
+        None => core.aptos_rest_client.submit_transaction(transaction.clone(), signature_payload).await,
+    };
+
+    let result = match (result, &transaction_hash) {
+        (Err(AptosError::InvalidRequest), Some(hash)) => match core.aptos_rest_client.get_transaction(hash).await {
+            Ok(known_transaction) => Ok(known_transaction),
+            Err(_) => Err(AptosError::InvalidRequest),
+        },
+        (result, _) => result,
+    };
+
+    if let (Ok(pending_transaction), Some(hash)) = (&result, &transaction_hash) {
+        if pending_transaction.hash != *hash {
+            log::error!("Transaction hash {} computed as {}", pending_transaction.hash, hash);
+        }
+    }
+
+    if let Some((sender, sequence_number)) = sequence_number {
+        match (&result, &transaction_hash) {
+            (Ok(pending_transaction), _) => core.sequence_numbers.submitted(&sender, sequence_number, &pending_transaction.hash, &transaction),
+            (Err(AptosError::InvalidRequest), Some(hash)) => core.sequence_numbers.submitted(&sender, sequence_number, hash, &transaction),
+            (Err(_), _) => core.sequence_numbers.release(&sender, sequence_number),
+        };
+    }
+
+    return result;
+}
+
+/// The generated transaction won't be submitted, its sequence number is handed out again
+fn release_transaction(core: &Core, transaction: &str) {
+    if let Some((sender, sequence_number)) = sequence::transaction_sequence_number(transaction) {
+        core.sequence_numbers.release(&sender, sequence_number);
+    }
+}
+
+/// Waits for the transaction to be committed, its sequence number is then used
+async fn wait_for_transaction(core: &Core, transaction_hash: &str) -> Result<model::Transaction, AptosError> {
+    let result = core.aptos_rest_client.wait_for_transaction(transaction_hash).await;
+
+    if let Ok(transaction) = &result {
+        let sender = transaction.sender.as_deref().map(AccountAddress::parse);
+
+        if let (Some(Ok(sender)), Ok(sequence_number)) = (sender, transaction.sequence_number.parse::<u64>()) {
+            core.sequence_numbers.committed(&sender, sequence_number);
+        }
+    }
+
+    return result;
+}
+
+fn account_keys_signer(account_keys: &AccountKeys) -> Result<AccountSigner, AptosError> {
+    return account_signer(account_keys.kind(), &account_keys.public_keys, account_keys.threshold, &[]);
+}
+
+fn signer_from_spec(core: &Core, signer_spec: &SignerSpec) -> Result<Box<dyn Signer>, AptosError> {
+    return signer(core, signer_spec.kind(), &signer_spec.keypair, &signer_spec.public_key, &signer_spec.keystore_path, &signer_spec.keystore_password);
+}
+
+fn signers_from_specs(core: &Core, signer_specs: &[SignerSpec]) -> Result<Vec<Box<dyn Signer>>, AptosError> {
+    let mut signers: Vec<Box<dyn Signer>> = Vec::new();
+
+    for signer_spec in signer_specs {
+        match signer_from_spec(core, signer_spec) {
+            Ok(signer) => signers.push(signer),
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(signers);
+}
+
+fn signer_refs(signers: &[Box<dyn Signer>]) -> Vec<&dyn Signer> {
+    return signers.iter().map(|signer| signer.as_ref()).collect();
+}
+
+/// Signs the message with every signer
+fn sign_all(signers: &[&dyn Signer], message: &[u8]) -> Result<Vec<crate::aptos::SignedPayload>, AptosError> {
+    let mut signed_payloads: Vec<crate::aptos::SignedPayload> = Vec::new();
+
+    for signer in signers {
+        match signer.sign(message) {
+            Ok(signature) => signed_payloads.push(crate::aptos::SignedPayload {
+                public_key: signer.public_key(),
+                signature: signature,
+            }),
+            Err(error) => return Err(error),
+        }
+    }
+
+    return Ok(signed_payloads);
+}
+
+/// Signs the local signing message of the transaction with every signer,
+/// the signatures are verified before the authenticator is built so that nothing invalid is submitted
+async fn sign_transaction(
+    core: &Core,
+    account_signer: &AccountSigner,
+    signers: &[&dyn Signer],
+    transaction: &str,
+) -> Result<serde_json::Value, AptosError> {
+    let chain_id = match core.aptos_rest_client.chain_id().await {
+        Ok(chain_id) => chain_id,
+        Err(error) => return Err(error),
+    };
+
+    let parameter_types = match core.module_abis.parameter_types(&core.aptos_rest_client, transaction).await {
+        Ok(parameter_types) => parameter_types,
+        Err(error) => return Err(error),
+    };
+
+    let signing_message = match signing_message(transaction, chain_id, &parameter_types) {
+        Ok(signing_message) => signing_message,
+        Err(error) => return Err(error),
+    };
+
+    let signed_payloads = match sign_all(signers, &signing_message) {
+        Ok(signed_payloads) => signed_payloads,
+        Err(error) => return Err(error),
+    };
+
+    return account_signer.verified_authenticator(&signing_message, &signed_payloads);
+}
+
+/// Signs the transaction with every signer of the account, submits it and waits for it to be committed
+async fn sign_and_submit(
+    core: &Core,
+    account_signer: &AccountSigner,
+    signers: &[Box<dyn Signer>],
+    transaction: String,
+) -> Result<model::Transaction, AptosError> {
+    let signature_payload = match sign_transaction(core, account_signer, &signer_refs(signers), &transaction).await {
+        Ok(signature_payload) => signature_payload,
+        Err(error) => {
+            release_transaction(core, &transaction);
+            return Err(error)
+        },
+    };
+
+    let transaction_hash = local_transaction_hash(core, &transaction, &signature_payload).await;
+
+    let pending_transaction = match submit_transaction(core, transaction, signature_payload, transaction_hash).await {
+        Ok(pending_transaction) => pending_transaction,
+        Err(error) => return Err(error),
+    };
+
+    return wait_for_transaction(core, &pending_transaction.hash).await;
+}
+
+pub async fn handle_rotate_authentication_key(core: Arc<Core>, req: RotateAuthenticationKeyRequest) -> Box<RotateAuthenticationKeyResponse> {
+    let response = match rotate_authentication_key(&core, req).await {
+        Ok(response) => response,
+        Err(error) => RotateAuthenticationKeyResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn rotate_authentication_key(core: &Arc<Core>, req: RotateAuthenticationKeyRequest) -> Result<RotateAuthenticationKeyResponse, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let from = match &req.from {
+        Some(account_keys) => account_keys_signer(account_keys),
+        None => core.account_registry.get(&address).ok_or(AptosError::UnknownAccount),
+    };
+
+    let from = match from {
+        Ok(from) => from,
+        Err(error) => return Err(error),
+    };
+
+    let to = match account_keys_signer(&req.to.clone().unwrap_or_default()) {
+        Ok(to) => to,
+        Err(error) => return Err(error),
+    };
+
+    let to_authentication_key = match to.authentication_key() {
+        Ok(authentication_key) => authentication_key,
+        Err(error) => return Err(error),
+    };
+
+    let from_signers = match signers_from_specs(core, &req.from_signers) {
+        Ok(signers) => signers,
+        Err(error) => return Err(error),
+    };
+
+    let to_signers = match signers_from_specs(core, &req.to_signers) {
+        Ok(signers) => signers,
+        Err(error) => return Err(error),
+    };
+
+    let account = match core.aptos_rest_client.get_account(&address).await {
+        Ok(account) => account,
+        Err(error) => return Err(error),
+    };
+
+    let current_authentication_key = match AccountAddress::parse(&account.authentication_key) {
+        Ok(authentication_key) => authentication_key,
+        Err(error) => return Err(error),
+    };
+
+    if let Err(error) = core.aptos_rest_client.ensure_clock_synced().await {
+        return Err(error);
+    }
+
+    // The challenge is signed for the sequence number of the rotation transaction
+    let sequence_number = match core.sequence_numbers.allocate(&address).await {
+        Ok(sequence_number) => sequence_number,
+        Err(error) => return Err(error),
+    };
+
+    let transaction = rotation_transaction(
+        core,
+        &address,
+        sequence_number,
+        &current_authentication_key,
+        (&from, &from_signers),
+        (&to, &to_signers),
+    );
+
+    let transaction = match transaction {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            core.sequence_numbers.release(&address, sequence_number);
+            return Err(error)
+        },
+    };
+
+    core.sequence_numbers.generated(&address, sequence_number, &transaction);
+
+    let committed_transaction = match sign_and_submit(core, &from, &from_signers, transaction).await {
+        Ok(committed_transaction) => committed_transaction,
+        Err(error) => return Err(error),
+    };
+
+    let success = committed_transaction.success == Some(true);
+
+    if success {
+        if let Err(error) = core.account_registry.rotate(&address, to) {
+            log::error!("Rotated keys of {} not stored: {:?}", address, error);
+        }
+    }
+
+    return Ok(RotateAuthenticationKeyResponse {
+        transaction: Some(Transaction {
+            type_transaction: committed_transaction.type_transaction.clone(),
+            hash: committed_transaction.hash.clone(),
+        }),
+        success: success,
+        authentication_key: to_authentication_key.to_string(),
+        ..Default::default()
+    });
+}
+
+/// Generates the rotation transaction with the proof challenge signed by the current and the new keys
+fn rotation_transaction(
+    core: &Core,
+    address: &AccountAddress,
+    sequence_number: u64,
+    current_authentication_key: &AccountAddress,
+    (from, from_signers): (&AccountSigner, &[Box<dyn Signer>]),
+    (to, to_signers): (&AccountSigner, &[Box<dyn Signer>]),
+) -> Result<String, AptosError> {
+    let to_public_key_material = match to.public_key_material() {
+        Ok(material) => material,
+        Err(error) => return Err(error),
+    };
+
+    let challenge = rotation_proof_challenge(sequence_number, address, current_authentication_key, &to_public_key_material);
+
+    let cap_rotate_key = match sign_all(&signer_refs(from_signers), &challenge).and_then(|signed_payloads| from.signature_bytes(&signed_payloads)) {
+        Ok(signature_bytes) => signature_bytes,
+        Err(error) => return Err(error),
+    };
+
+    let cap_update_table = match sign_all(&signer_refs(to_signers), &challenge).and_then(|signed_payloads| to.signature_bytes(&signed_payloads)) {
+        Ok(signature_bytes) => signature_bytes,
+        Err(error) => return Err(error),
+    };
+
+    let payload = match rotate_authentication_key_payload(from, to, &cap_rotate_key, &cap_update_table) {
+        Ok(payload) => payload,
+        Err(error) => return Err(error),
+    };
+
+    return core.aptos_rest_client.generate_transaction_at(address, payload.to_payload(), sequence_number, DEFAULT_MAX_GAS_AMOUNT, DEFAULT_GAS_UNIT_PRICE);
+}
+
+/// Publishes a Move package from the account, the ABIs of its modules are fetched again on the next call
+pub async fn handle_publish_package(core: Arc<Core>, req: PublishPackageRequest) -> Box<PublishPackageResponse> {
+    let response = match publish_package(&core, &req).await {
+        Ok(committed_transaction) => PublishPackageResponse {
+            transaction: Some(Transaction {
+                type_transaction: committed_transaction.type_transaction.clone(),
+                hash: committed_transaction.hash.clone(),
+            }),
+            success: committed_transaction.success == Some(true),
+            vm_status: committed_transaction.vm_status.clone().unwrap_or_default(),
+            ..Default::default()
+        },
+        Err(error) => PublishPackageResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn publish_package(core: &Core, req: &PublishPackageRequest) -> Result<model::Transaction, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let payload = publish_package_payload(&req.package_metadata, &req.modules).to_payload();
+
+    let max_gas_amount = match req.max_gas_amount {
+        0 => PUBLISH_MAX_GAS_AMOUNT,
+        max_gas_amount => max_gas_amount,
+    };
+
+    let committed_transaction = match send_payload(core, &address, &req.keys, &req.signers, payload, max_gas_amount).await {
+        Ok(committed_transaction) => committed_transaction,
+        Err(error) => return Err(error),
+    };
+
+    if committed_transaction.success == Some(true) {
+        core.module_abis.invalidate(&address);
+    }
+
+    return Ok(committed_transaction);
+}
+
+/// Sends the payload from the account, signed by the given signers with its registered keys unless given
+async fn send_payload(
+    core: &Core,
+    address: &AccountAddress,
+    keys: &Option<AccountKeys>,
+    signer_specs: &[SignerSpec],
+    payload: serde_json::Value,
+    max_gas_amount: u64,
+) -> Result<model::Transaction, AptosError> {
+    let account_signer = match keys {
+        Some(account_keys) => account_keys_signer(account_keys),
+        None => core.account_registry.get(address).ok_or(AptosError::UnknownAccount),
+    };
+
+    let account_signer = match account_signer {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let signers = match signers_from_specs(core, signer_specs) {
+        Ok(signers) => signers,
+        Err(error) => return Err(error),
+    };
+
+    let transaction = match generate_transaction(core, address, payload, max_gas_amount).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(error),
+    };
+
+    return sign_and_submit(core, &account_signer, &signers, transaction).await;
+}
+
+/// Publishes the coin store of the coin so the account can receive it
+pub async fn handle_register_coin(core: Arc<Core>, req: RegisterCoinRequest) -> Box<RegisterCoinResponse> {
+    let response = match register_coin(&core, &req).await {
+        Ok(committed_transaction) => RegisterCoinResponse {
+            transaction: Some(Transaction {
+                type_transaction: committed_transaction.type_transaction.clone(),
+                hash: committed_transaction.hash.clone(),
+            }),
+            success: committed_transaction.success == Some(true),
+            vm_status: committed_transaction.vm_status.clone().unwrap_or_default(),
+            ..Default::default()
+        },
+        Err(error) => RegisterCoinResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn register_coin(core: &Core, req: &RegisterCoinRequest) -> Result<model::Transaction, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let coin_type = match TypeTag::parse(&req.coin_type) {
+        Ok(coin_type) => coin_type,
+        Err(error) => return Err(error),
+    };
+
+    let payload = register_coin_payload(&coin_type).to_payload();
+
+    return send_payload(core, &address, &req.keys, &req.signers, payload, DEFAULT_MAX_GAS_AMOUNT).await;
+}
+
+pub async fn handle_is_coin_registered(core: Arc<Core>, req: IsCoinRegisteredRequest) -> Box<IsCoinRegisteredResponse> {
+    let response = match is_coin_registered(&core, &req).await {
+        Ok(registered) => IsCoinRegisteredResponse {
+            registered: registered,
+            ..Default::default()
+        },
+        Err(error) => IsCoinRegisteredResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn is_coin_registered(core: &Core, req: &IsCoinRegisteredRequest) -> Result<bool, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let coin_type = match TypeTag::parse(&req.coin_type) {
+        Ok(coin_type) => coin_type,
+        Err(error) => return Err(error),
+    };
+
+    return core.aptos_rest_client.is_coin_registered(&address, &coin_type).await;
+}
+
+/// Returns the transactions of the account waiting in the mempool and the sequence numbers blocking them
+pub fn handle_get_pending_transactions(core: Arc<Core>, req: GetPendingTransactionsRequest) -> Box<GetPendingTransactionsResponse> {
+    let response = match get_pending_transactions(&core, &req) {
+        Ok(response) => response,
+        Err(error) => GetPendingTransactionsResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+fn get_pending_transactions(core: &Core, req: &GetPendingTransactionsRequest) -> Result<GetPendingTransactionsResponse, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let now = match core.aptos_rest_client.clock().now_secs() {
+        Ok(now) => now,
+        Err(error) => return Err(error),
+    };
+
+    let pending = core.sequence_numbers.pending(&address)
+        .iter()
+        .map(|pending| PendingTransaction {
+            sequence_number: pending.sequence_number,
+            hash: pending.hash.clone().unwrap_or_default(),
+            expiration_timestamp_secs: pending.expiration_secs,
+            expired: pending.expiration_secs <= now,
+        })
+        .collect();
+
+    return Ok(GetPendingTransactionsResponse {
+        pending: pending,
+        gaps: core.sequence_numbers.gaps(&address),
+        ..Default::default()
+    });
+}
+
+/// Sends a transaction with the sequence number of a pending one and a higher gas unit price to unblock the account,
+/// the same transaction or a transfer of 0 to the account itself
+pub async fn handle_replace_transaction(core: Arc<Core>, req: ReplaceTransactionRequest) -> Box<ReplaceTransactionResponse> {
+    let response = match replace_transaction(&core, &req).await {
+        Ok(response) => response,
+        Err(error) => ReplaceTransactionResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+/// Pending transactions are only known until the core is freed, after a restart a transaction can only be replaced
+/// by a transfer of 0, the gas unit price and max gas amount of the pending one are then unknown and must be given
+async fn replace_transaction(core: &Core, req: &ReplaceTransactionRequest) -> Result<ReplaceTransactionResponse, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let account_signer = match &req.keys {
+        Some(account_keys) => account_keys_signer(account_keys),
+        None => core.account_registry.get(&address).ok_or(AptosError::UnknownAccount),
+    };
+
+    let account_signer = match account_signer {
+        Ok(account_signer) => account_signer,
+        Err(error) => return Err(error),
+    };
+
+    let signers = match signers_from_specs(core, &req.signers) {
+        Ok(signers) => signers,
+        Err(error) => return Err(error),
+    };
+
+    let pending = core.sequence_numbers.pending(&address)
+        .into_iter()
+        .find(|pending| pending.sequence_number == req.sequence_number);
+
+    let replaced_transaction: Option<serde_json::Value> = pending.as_ref()
+        .and_then(|pending| pending.transaction.as_ref())
+        .and_then(|transaction| serde_json::from_str(transaction).ok());
+
+    let payload = match (req.replacement(), &replaced_transaction) {
+        (Replacement::SameTransaction, Some(transaction)) => transaction["payload"].clone(),
+        (Replacement::SameTransaction, None) => {
+            log::error!("Transaction {} of {} is not known by the core", req.sequence_number, address);
+            return Err(AptosError::UnknownTransaction)
+        },
+        (Replacement::NoOp, _) => transfer_payload(&address, 0),
+    };
+
+    let transaction_field = |field: &str| replaced_transaction.as_ref()
+        .and_then(|transaction| transaction[field].as_str())
+        .and_then(|value| value.parse::<u64>().ok());
+
+    let previous_gas = match &replaced_transaction {
+        Some(_) => match (transaction_field("gas_unit_price"), transaction_field("max_gas_amount")) {
+            (Some(gas_unit_price), Some(max_gas_amount)) => Some((gas_unit_price, max_gas_amount)),
+            _ => {
+                log::error!("Transaction {} of {} has no valid gas fields", req.sequence_number, address);
+                return Err(AptosError::InvalidTransaction)
+            },
+        },
+        None => None,
+    };
+
+    let (gas_unit_price, max_gas_amount) = match replacement_gas(req.gas_unit_price, req.max_gas_amount, previous_gas) {
+        Ok(gas) => gas,
+        Err(error) => return Err(error),
+    };
+
+    if let Err(error) = core.aptos_rest_client.ensure_clock_synced().await {
+        return Err(error);
+    }
+
+    let transaction = match core.aptos_rest_client.generate_transaction_at(&address, payload, req.sequence_number, max_gas_amount, gas_unit_price) {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(error),
+    };
+
+    let committed_transaction = match sign_and_submit(core, &account_signer, &signers, transaction).await {
+        Ok(committed_transaction) => committed_transaction,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(ReplaceTransactionResponse {
+        transaction: Some(Transaction {
+            type_transaction: committed_transaction.type_transaction.clone(),
+            hash: committed_transaction.hash.clone(),
+        }),
+        success: committed_transaction.success == Some(true),
+        vm_status: committed_transaction.vm_status.clone().unwrap_or_default(),
+        replaced_hash: pending.and_then(|pending| pending.hash).unwrap_or_default(),
+        gas_unit_price: gas_unit_price,
+        ..Default::default()
+    });
+}
+
+/// Gas unit price and max gas amount of a replacement, from the ones requested and the ones of the pending transaction when known.
+/// The mempool only replaces a transaction by one paying more, the price is twice the pending one unless given.
+fn replacement_gas(
+    gas_unit_price: u64,
+    max_gas_amount: u64,
+    previous_gas: Option<(u64, u64)>,
+) -> Result<(u64, u64), AptosError> {
+    let gas_unit_price = match (gas_unit_price, previous_gas) {
+        (0, Some((previous_gas_unit_price, _))) => match previous_gas_unit_price.checked_mul(2) {
+            Some(gas_unit_price) => gas_unit_price,
+            None => {
+                log::error!("Gas unit price {} can't be doubled", previous_gas_unit_price);
+                return Err(AptosError::InvalidGasPrice)
+            },
+        },
+        (0, None) => {
+            log::error!("Gas unit price of the pending transaction is unknown, it must be given");
+            return Err(AptosError::InvalidGasPrice)
+        },
+        (gas_unit_price, Some((previous_gas_unit_price, _))) if gas_unit_price <= previous_gas_unit_price => {
+            log::error!("Gas unit price {} not above {}", gas_unit_price, previous_gas_unit_price);
+            return Err(AptosError::InvalidGasPrice)
+        },
+        (gas_unit_price, _) => gas_unit_price,
+    };
+
+    let max_gas_amount = match (max_gas_amount, previous_gas) {
+        (0, Some((_, previous_max_gas_amount))) => previous_max_gas_amount,
+        (0, None) => {
+            log::error!("Max gas amount of the pending transaction is unknown, it must be given");
+            return Err(AptosError::InvalidTransaction)
+        },
+        (max_gas_amount, _) => max_gas_amount,
+    };
+
+    return Ok((gas_unit_price, max_gas_amount));
+}
+
+fn transaction_record(core: &Core, transaction: &CachedTransaction) -> TransactionRecord {
+    return TransactionRecord {
+        hash: transaction.hash.clone(),
+        sequence_number: transaction.sequence_number,
+        version: transaction.version,
+        timestamp: transaction.timestamp,
+        sender: transaction.sender.to_string(),
+        counterparty: transaction.counterparty.map(|address| address.to_string()).unwrap_or_default(),
+        function: transaction.function.clone(),
+        status: match transaction.success {
+            true => TransactionStatus::TransactionSucceeded as i32,
+            false => TransactionStatus::TransactionFailed as i32,
+        },
+        vm_status: transaction.vm_status.clone(),
+        amount: transaction.amount.unwrap_or(0),
+        coin_type: transaction.coin_type.clone(),
+        gas_fee: transaction.gas_fee,
+        counterparty_label: transaction.counterparty
+            .map(|address| address_label(core, &address))
+            .unwrap_or_default(),
+        incoming: transaction.incoming,
+    };
+}
+
+pub async fn handle_get_wallet_transactions(core: Arc<Core>, req: GetWalletTransactionsRequest)  -> Box<GetWalletTransactionsResponse> {
+    let response = match get_wallet_transactions(&core, req).await {
+        Ok(response) => response,
+        Err(error) => GetWalletTransactionsResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn get_wallet_transactions(core: &Arc<Core>, req: GetWalletTransactionsRequest) -> Result<GetWalletTransactionsResponse, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let counterparty = match req.counterparty.is_empty() {
+        true => None,
+        false => match AccountAddress::parse(&req.counterparty) {
+            Ok(counterparty) => Some(counterparty),
+            Err(error) => return Err(error),
+        },
+    };
+
+    // The cached transactions are served when the node can't be reached
+    let synced = match req.offline {
+        true => false,
+        false => match core.transaction_history.sync(&core.aptos_rest_client, &address).await {
+            Ok(_) => true,
+            Err(error) => {
+                log::warn!("Transactions of {} not synced: {:?}", address, error);
+                false
+            },
+        },
+    };
+
+    let filter = HistoryFilter {
+        start_time: Some(req.start_time).filter(|time| *time > 0),
+        end_time: Some(req.end_time).filter(|time| *time > 0),
+        counterparty: counterparty,
+        status: match req.status() {
+            TransactionStatus::AnyStatus => TransactionStatusFilter::Any,
+            TransactionStatus::TransactionSucceeded => TransactionStatusFilter::Success,
+            TransactionStatus::TransactionFailed => TransactionStatusFilter::Failure,
+        },
+        start_version: None,
+        end_version: None,
+        coin_type: None,
+    };
+
+    let transactions = core.transaction_history.query(&address, &filter);
+
+    // Not committed yet, so only listed when the transactions are not filtered
+    let unfiltered = req.status() == TransactionStatus::AnyStatus && counterparty.is_none() && req.end_time == 0;
+
+    let mut pending = match unfiltered {
+        true => core.sequence_numbers.pending(&address),
+        false => Vec::new(),
+    };
+
+    pending.sort_by_key(|pending| std::cmp::Reverse(pending.sequence_number));
+
+    let pending_transactions = pending.into_iter()
+        .filter_map(|pending| pending.hash)
+        .filter(|hash| !transactions.iter().any(|t| t.hash == *hash))
+        .map(|hash| Transaction {
+            type_transaction: String::from("pending_transaction"),
+            hash: hash,
+        });
+
+    let committed_transactions = transactions.iter()
+        .filter(|t| !t.incoming || !transactions.iter().any(|other| !other.incoming && other.hash == t.hash))
+        .map(|t| Transaction {
+            type_transaction: t.type_transaction.clone(),
+            hash: t.hash.clone(),
+        });
+
+    return Ok(GetWalletTransactionsResponse {
+        transactions: pending_transactions.chain(committed_transactions).collect(),
+        records: transactions.iter().map(|t| transaction_record(core, t)).collect(),
+        synced: synced,
+        ..Default::default()
+    });
+}
+
+/// Writes a statement of the account, the history is synced first when the node can be reached
+pub async fn handle_export_transactions(core: Arc<Core>, req: ExportTransactionsRequest) -> Box<ExportTransactionsResponse> {
+    let response = match export_transactions(&core, req).await {
+        Ok(response) => response,
+        Err(error) => ExportTransactionsResponse {
+            error: Some(core_error(error)),
+            ..Default::default()
+        },
+    };
+
+    return Box::new(response);
+}
+
+async fn export_transactions(core: &Arc<Core>, req: ExportTransactionsRequest) -> Result<ExportTransactionsResponse, AptosError> {
+    let address = match AccountAddress::parse(&req.address) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    let coin_type = match req.coin_type.is_empty() {
+        true => None,
+        false => match TypeTag::parse(&req.coin_type) {
+            Ok(coin_type) => Some(coin_type.to_string()),
+            Err(error) => return Err(error),
+        },
+    };
+
+    let synced = match core.transaction_history.sync(&core.aptos_rest_client, &address).await {
+        Ok(_) => true,
+        Err(error) => {
+            log::warn!("Transactions of {} not synced: {:?}", address, error);
+            false
+        },
+    };
+
+    let filter = HistoryFilter {
+        start_time: Some(req.start_time).filter(|time| *time > 0),
+        end_time: Some(req.end_time).filter(|time| *time > 0),
+        counterparty: None,
+        status: TransactionStatusFilter::Any,
+        start_version: Some(req.start_version).filter(|version| *version > 0),
+        end_version: Some(req.end_version).filter(|version| *version > 0),
+        coin_type: coin_type,
+    };
+
+    // Statements are in chronological order
+    let mut transactions = core.transaction_history.query(&address, &filter);
+    transactions.reverse();
+
+    let format = match req.format() {
+        StatementFormat::Csv => export::ExportFormat::Csv,
+        StatementFormat::Json => export::ExportFormat::Json,
+    };
+
+    let count = match export::export_transactions(&transactions, format, Path::new(&req.path)) {
+        Ok(count) => count,
+        Err(error) => return Err(error),
+    };
+
+    return Ok(ExportTransactionsResponse {
+        count: count as u32,
+        synced: synced,
+        ..Default::default()
+    });
+}
+
+/// Waits in the background for the transaction to be committed and notifies the host with a `TransactionConfirmed` event
+fn spawn_confirmation_watch(core: Arc<Core>, transaction_hash: String) {
+    // Locked until the task is recorded, the task removes itself once done
+    let mut confirmation_watches = core.confirmation_watches.lock().unwrap();
+
+    let task_core = core.clone();
+    let task_hash = transaction_hash.clone();
+
+    let task = tokio::spawn(async move {
+        match wait_for_transaction(&task_core, &task_hash).await {
+            Ok(transaction) => task_core.event_emitter.emit(event::Kind::TransactionConfirmed(TransactionConfirmedEvent {
+                transaction: Some(Transaction {
+                    type_transaction: transaction.type_transaction.clone(),
+                    hash: transaction.hash.clone(),
+                }),
+                success: transaction.success.unwrap_or(false),
+            })),
+            Err(error) => log::error!("Transaction {} not confirmed: {:?}", task_hash, error),
+        };
+
+        task_core.confirmation_watches.lock().unwrap().remove(&task_hash);
+    });
+
+    // The same transaction submitted again is watched once
+    if let Some(previous_task) = confirmation_watches.insert(transaction_hash, task) {
+        previous_task.abort();
+    }
+}
+
+fn transfer_error(stage: TransferStage, error: AptosError) -> TransferError {
+    let kind = match error {
+        AptosError::InvalidKeypair => TransferErrorKind::InvalidKeypair,
+        AptosError::InvalidRequest => TransferErrorKind::RequestFailed,
+        AptosError::InvalidJSON | AptosError::InvalidResponse => TransferErrorKind::InvalidResponse,
+        AptosError::InvalidSequenceNumber => TransferErrorKind::InvalidSequenceNumber,
+        AptosError::TimeWentBackwards => TransferErrorKind::InvalidClock,
+        AptosError::TransactionTimeout => TransferErrorKind::ConfirmationTimeout,
+        AptosError::InvalidAddress => TransferErrorKind::InvalidAddress,
+        AptosError::InvalidTypeTag => TransferErrorKind::InvalidCoinType,
+        AptosError::RecipientNotRegistered => TransferErrorKind::RecipientNotRegistered,
+        _ => TransferErrorKind::UnknownError,
+    };
+
+    return TransferError {
+        stage: stage as i32,
+        kind: kind as i32,
+        message: format!("{:?}", error),
+    };
+}
+
+/// Transfers with `coin::transfer` to registered recipients, otherwise with `aptos_account::transfer_coins`
+/// which registers them, unless the recipient is required to be registered
+async fn coin_payload(
+    core: &Core,
+    address_to: &AccountAddress,
+    amount: u64,
+    coin_type: &str,
+    require_registered_recipient: bool,
+) -> Result<serde_json::Value, AptosError> {
+    let coin_type = match TypeTag::parse(coin_type) {
+        Ok(coin_type) => coin_type,
+        Err(error) => return Err(error),
+    };
+
+    let registered = match core.aptos_rest_client.is_coin_registered(address_to, &coin_type).await {
+        Ok(registered) => registered,
+        Err(error) => return Err(error),
+    };
+
+    if registered {
+        return Ok(coin_transfer_payload(&coin_type, address_to, amount).to_payload());
+    }
+
+    if require_registered_recipient {
+        log::error!("{} can't receive {}", address_to, coin_type);
+        return Err(AptosError::RecipientNotRegistered);
+    }
+
+    log::warn!("{} can't receive {} yet, its coin store is created by the transfer", address_to, coin_type);
+
+    return Ok(transfer_coins_payload(&coin_type, address_to, amount).to_payload());
+}
+
+/// Account sending a transfer and its address, derived from the keypair when not given
+fn transfer_sender(core: &Core, keypair: &str, address_from: &str) -> Result<(AptosAccount, AccountAddress), AptosError> {
+    let account_from = match AptosAccount::from_keypair(keypair) {
+        Ok(account) => account,
+        Err(error) => return Err(error),
+    };
+
+    // The address differs from the authentication key once the key is rotated
+    let address_from = match address_from.is_empty() {
+        true => core.account_registry
+            .address_of(&AccountSigner::single(&account_from.public_key()))
+            .unwrap_or(account_from.address()),
+        false => match AccountAddress::parse(address_from) {
+            Ok(address) => address,
+            Err(error) => return Err(error),
+        },
+    };
+
+    return Ok((account_from, address_from));
+}
+
+/// Payload sending the amount to the recipient, AptosCoin when the coin type is empty
+async fn transfer_entry_payload(
+    core: &Core,
+    address_to: &str,
+    amount: u64,
+    coin_type: &str,
+    require_registered_recipient: bool,
+) -> Result<serde_json::Value, AptosError> {
+    let address_to = match AccountAddress::parse(address_to) {
+        Ok(address) => address,
+        Err(error) => return Err(error),
+    };
+
+    return match coin_type.is_empty() {
+        true => Ok(transfer_payload(&address_to, amount)),
+        false => coin_payload(core, &address_to, amount, coin_type, require_registered_recipient).await,
+    };
+}
+
+/// Runs the transfer pipeline, `on_stage` is called before each stage with the transaction hash once known
+async fn run_transfer<F: Fn(TransferStage, &str)>(
+    core: &Core,
+    req: &TransferRequest,
+    on_stage: F,
+) -> Result<Transaction, TransferError> {
+    on_stage(TransferStage::Building, "");
+
+    let (account_from, address_from) = match transfer_sender(core, &req.keypair, &req.address_from) {
+        Ok(sender) => sender,
+        Err(error) => return Err(transfer_error(TransferStage::Building, error)),
+    };
+
+    let payload = match transfer_entry_payload(core, &req.address_to, req.amount, &req.coin_type, req.require_registered_recipient).await {
+        Ok(payload) => payload,
+        Err(error) => return Err(transfer_error(TransferStage::Building, error)),
+    };
+
+    let transaction = match generate_transaction(core, &address_from, payload, DEFAULT_MAX_GAS_AMOUNT).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(transfer_error(TransferStage::Building, error)),
+    };
+
+    return complete_transfer(core, &account_from, transaction, on_stage).await;
+}
+
+/// Signs, submits and confirms a generated transfer transaction
+async fn complete_transfer<F: Fn(TransferStage, &str)>(
+    core: &Core,
+    account_from: &AptosAccount,
+    transaction: String,
+    on_stage: F,
+) -> Result<Transaction, TransferError> {
+    let transaction_hash = match submit_transfer(core, account_from, transaction, &on_stage).await {
+        Ok(transaction_hash) => transaction_hash,
+        Err(error) => return Err(error),
+    };
+
+    return confirm_transfer(core, &transaction_hash, &on_stage).await;
+}
+
+/// Signs and submits a generated transfer transaction, returns its hash once accepted by the node
+async fn submit_transfer<F: Fn(TransferStage, &str)>(
+    core: &Core,
+    account_from: &AptosAccount,
+    transaction: String,
+    on_stage: &F,
+) -> Result<String, TransferError> {
+    on_stage(TransferStage::Signing, "");
+
+    let signature_payload = match sign_transaction(core, &AccountSigner::single(&account_from.public_key()), &[account_from], &transaction).await {
+        Ok(signature_payload) => signature_payload,
+        Err(error) => {
+            release_transaction(core, &transaction);
+            return Err(transfer_error(TransferStage::Signing, error))
+        },
+    };
+
+    // Reported before the submission, the host can look the transaction up if the submission times out
+    let transaction_hash = local_transaction_hash(core, &transaction, &signature_payload).await;
+
+    on_stage(TransferStage::Submitting, transaction_hash.as_deref().unwrap_or(""));
+
+    let pending_transaction = match submit_transaction(core, transaction, signature_payload, transaction_hash).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(transfer_error(TransferStage::Submitting, error)),
+    };
+
+    return Ok(pending_transaction.hash);
+}
+
+/// Waits for a submitted transfer to be committed, fails when it was not executed successfully
+async fn confirm_transfer<F: Fn(TransferStage, &str)>(
+    core: &Core,
+    transaction_hash: &str,
+    on_stage: &F,
+) -> Result<Transaction, TransferError> {
+    on_stage(TransferStage::Confirming, transaction_hash);
+
+    let committed_transaction = match wait_for_transaction(core, transaction_hash).await {
+        Ok(transaction) => transaction,
+        Err(error) => return Err(transfer_error(TransferStage::Confirming, error)),
+    };
+
+    if committed_transaction.success != Some(true) {
+        return Err(TransferError {
+            stage: TransferStage::Confirming as i32,
+            kind: TransferErrorKind::ExecutionFailed as i32,
+            message: committed_transaction.vm_status.clone().unwrap_or_default(),
+        });
+    }
+
+    return Ok(Transaction {
+        type_transaction: committed_transaction.type_transaction.clone(),
+        hash: committed_transaction.hash.clone(),
+    });
+}
+
+pub async fn handle_transfer(core: Arc<Core>, req: TransferRequest) -> Box<TransferResponse> {
+    let emit_stage = |stage: TransferStage, transaction_hash: &str| {
+        core.event_emitter.emit(event::Kind::TransferProgress(TransferProgressEvent {
+            transfer_id: req.transfer_id.clone(),
+            stage: stage as i32,
+            transaction_hash: transaction_hash.to_string(),
+        }));
+    };
+
+    let result = match run_transfer(&core, &req, emit_stage).await {
+        Ok(transaction) => {
+            emit_stage(TransferStage::Committed, &transaction.hash);
+
+            transfer_response::Result::Transaction(transaction)
+        },
+        Err(error) => {
+            log::error!("Transfer {} failed: {}", req.transfer_id, error.message);
+            emit_stage(TransferStage::Failed, "");
+
+            transfer_response::Result::Error(error)
+        },
+    };
+
+    let response = TransferResponse {
+        result: Some(result),
+        ..Default::default()
+    };
+
+    return Box::new(response);
+}
+
+/// Sends every entry from the same account: the transactions get consecutive sequence numbers from the core,
+/// are submitted one after the other and confirmed in parallel, and each entry has its own result.
+/// They are not submitted in parallel: an entry can't be committed before the previous ones, so the entries after
+/// a rejected one are not submitted and fail as blocked, their sequence numbers are released.
+/// Each entry is reported with a `TransferProgressEvent` whose `transfer_id` is `batch_id/index`
+pub async fn handle_batch_transfer(core: Arc<Core>, req: BatchTransferRequest) -> Box<BatchTransferResponse> {
+    let emit_stage = |index: usize, stage: TransferStage, transaction_hash: &str| {
+        core.event_emitter.emit(event::Kind::TransferProgress(TransferProgressEvent {
+            transfer_id: format!("{}/{}", req.batch_id, index),
+            stage: stage as i32,
+            transaction_hash: transaction_hash.to_string(),
+        }));
+    };
+
+    let mut results: Vec<Option<Result<Transaction, TransferError>>> = vec![None; req.entries.len()];
+
+    let (account_from, address_from) = match transfer_sender(&core, &req.keypair, &req.address_from) {
+        Ok(sender) => sender,
+        Err(error) => {
+            // Every entry fails the same way
+            let error = transfer_error(TransferStage::Building, error);
+            let results = (0..req.entries.len()).map(|_| Some(Err(error.clone()))).collect();
+
+            return Box::new(batch_transfer_response(&req, results, emit_stage));
+        },
+    };
+
+    let mut built: Vec<Result<String, AptosError>> = Vec::new();
+
+    for (index, entry) in req.entries.iter().enumerate() {
+        emit_stage(index, TransferStage::Building, "");
+
+        let payload = transfer_entry_payload(&core, &entry.address_to, entry.amount, &entry.coin_type, req.require_registered_recipients);
+
+        let transaction = match payload.await {
+            Ok(payload) => generate_transaction(&core, &address_from, payload, DEFAULT_MAX_GAS_AMOUNT).await,
+            Err(error) => Err(error),
+        };
+
+        built.push(transaction);
+    }
+
+    let plan = plan_batch(built);
+
+    for (index, error) in plan.failures {
+        results[index] = Some(Err(error));
+    }
+
+    let submit = |index: usize, transaction: String| {
+        let core = &core;
+        let account_from = &account_from;
+        let emit_stage = &emit_stage;
+
+        async move {
+            return submit_transfer(core, account_from, transaction, &|stage, transaction_hash| emit_stage(index, stage, transaction_hash)).await;
+        }
+    };
+
+    let submission = submit_in_order(plan.transactions, submit, |transaction| release_transaction(&core, transaction)).await;
+
+    for (index, error) in submission.failures {
+        results[index] = Some(Err(error));
+    }
+
+    let submitted = submission.submitted;
+
+    let confirmations = submitted.into_iter().map(|(index, transaction_hash)| {
+        let core = &core;
+        let emit_stage = &emit_stage;
+
+        async move {
+            let result = confirm_transfer(core, &transaction_hash, &|stage, transaction_hash| emit_stage(index, stage, transaction_hash)).await;
+
+            return (index, result);
+        }
+    });
+
+    for (index, result) in futures::future::join_all(confirmations).await {
+        results[index] = Some(result);
+    }
+
+    return Box::new(batch_transfer_response(&req, results, emit_stage));
+}
+
+/// Entries of a batch ready to be submitted and the ones that failed before
+struct BatchPlan {
+    /// Index and transaction of the built entries, in the order of their sequence numbers
+    transactions: Vec<(usize, String)>,
+    failures: Vec<(usize, TransferError)>,
+}
+
+/// Orders the built transactions of the entries by sequence number, entries failing to build don't take a number
+fn plan_batch(built: Vec<Result<String, AptosError>>) -> BatchPlan {
+    let mut transactions: Vec<(usize, String)> = Vec::new();
+    let mut failures: Vec<(usize, TransferError)> = Vec::new();
+
+    for (index, transaction) in built.into_iter().enumerate() {
+        match transaction {
+            Ok(transaction) => transactions.push((index, transaction)),
+            Err(error) => failures.push((index, transfer_error(TransferStage::Building, error))),
+        };
+    }
+
+    transactions.sort_by_key(|(_, transaction)| sequence::transaction_sequence_number(transaction).map(|(_, sequence_number)| sequence_number));
+
+    return BatchPlan {
+        transactions: transactions,
+        failures: failures,
+    };
+}
+
+/// Entries of a batch accepted by the node with their hash, and the ones that were rejected or blocked
+struct BatchSubmission {
+    submitted: Vec<(usize, String)>,
+    failures: Vec<(usize, TransferError)>,
+}
+
+/// Submits the transactions one after the other, the ones after a rejected transaction would wait for its
+/// sequence number: they are released instead of submitted and fail as blocked
+async fn submit_in_order<S, F, R>(transactions: Vec<(usize, String)>, submit: S, release: R) -> BatchSubmission
+where
+    S: Fn(usize, String) -> F,
+    F: std::future::Future<Output = Result<String, TransferError>>,
+    R: Fn(&str),
+{
+    let mut submitted: Vec<(usize, String)> = Vec::new();
+    let mut failures: Vec<(usize, TransferError)> = Vec::new();
+    let mut rejected_index: Option<usize> = None;
+
+    for (index, transaction) in transactions {
+        if let Some(rejected_index) = rejected_index {
+            release(&transaction);
+
+            failures.push((index, TransferError {
+                stage: TransferStage::Submitting as i32,
+                kind: TransferErrorKind::Blocked as i32,
+                message: format!("Blocked by entry {}", rejected_index),
+            }));
+
+            continue;
+        }
+
+        match submit(index, transaction).await {
+            Ok(transaction_hash) => submitted.push((index, transaction_hash)),
+            Err(error) => {
+                failures.push((index, error));
+                rejected_index = Some(index);
+            },
+        };
+    }
+
+    return BatchSubmission {
+        submitted: submitted,
+        failures: failures,
+    };
+}
+
+/// Reports the end of every entry, each one has a result
+fn batch_transfer_response<F: Fn(usize, TransferStage, &str)>(
+    req: &BatchTransferRequest,
+    results: Vec<Option<Result<Transaction, TransferError>>>,
+    emit_stage: F,
+) -> BatchTransferResponse {
+    let results: Vec<BatchTransferResult> = results.into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let result = match result.unwrap() {
+                Ok(transaction) => {
+                    emit_stage(index, TransferStage::Committed, &transaction.hash);
+
+                    batch_transfer_result::Result::Transaction(transaction)
+                },
+                Err(error) => {
+                    log::error!("Transfer {}/{} failed: {}", req.batch_id, index, error.message);
+                    emit_stage(index, TransferStage::Failed, "");
+
+                    batch_transfer_result::Result::Error(error)
+                },
+            };
+
+            return BatchTransferResult {
+                index: index as u32,
+                result: Some(result),
+            };
+        })
+        .collect();
+
+    return BatchTransferResponse {
+        succeeded: results.iter()
+            .filter(|result| matches!(result.result, Some(batch_transfer_result::Result::Transaction(_))))
+            .count() as u32,
+        results: results,
+        ..Default::default()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replacement_doubles_the_pending_gas_unit_price_unless_given() {
+        assert_eq!(replacement_gas(0, 0, Some((100, 2000))).unwrap(), (200, 2000));
+        assert_eq!(replacement_gas(150, 3000, Some((100, 2000))).unwrap(), (150, 3000));
+        assert!(matches!(replacement_gas(100, 0, Some((100, 2000))), Err(AptosError::InvalidGasPrice)));
+        assert!(matches!(replacement_gas(0, 0, Some((u64::MAX, 2000))), Err(AptosError::InvalidGasPrice)));
+    }
+
+    #[test]
+    fn replacement_of_an_unknown_transaction_needs_its_gas() {
+        assert_eq!(replacement_gas(150, 3000, None).unwrap(), (150, 3000));
+        assert!(matches!(replacement_gas(0, 3000, None), Err(AptosError::InvalidGasPrice)));
+        assert!(matches!(replacement_gas(150, 0, None), Err(AptosError::InvalidTransaction)));
+    }
+
+    #[test]
+    fn transfer_errors_keep_their_stage_and_kind() {
+        let cases = vec![
+            (AptosError::InvalidKeypair, TransferErrorKind::InvalidKeypair),
+            (AptosError::InvalidRequest, TransferErrorKind::RequestFailed),
+            (AptosError::InvalidJSON, TransferErrorKind::InvalidResponse),
+            (AptosError::InvalidResponse, TransferErrorKind::InvalidResponse),
+            (AptosError::InvalidSequenceNumber, TransferErrorKind::InvalidSequenceNumber),
+            (AptosError::TimeWentBackwards, TransferErrorKind::InvalidClock),
+            (AptosError::TransactionTimeout, TransferErrorKind::ConfirmationTimeout),
+            (AptosError::InvalidAddress, TransferErrorKind::InvalidAddress),
+            (AptosError::InvalidTypeTag, TransferErrorKind::InvalidCoinType),
+            (AptosError::RecipientNotRegistered, TransferErrorKind::RecipientNotRegistered),
+            (AptosError::NotFound, TransferErrorKind::UnknownError),
+        ];
+
+        for (error, kind) in cases {
+            let message = format!("{:?}", error);
+            let transfer_error = transfer_error(TransferStage::Submitting, error);
+
+            assert_eq!(transfer_error.stage, TransferStage::Submitting as i32);
+            assert_eq!(transfer_error.kind, kind as i32, "{}", message);
+            assert_eq!(transfer_error.message, message);
+        }
+    }
+
+    fn batch_transaction(sequence_number: u64) -> String {
+        return serde_json::json!({
+            "sender": "0xa",
+            "sequence_number": sequence_number.to_string(),
+        }).to_string();
+    }
+
+    #[test]
+    fn batch_is_planned_in_sequence_number_order() {
+        let plan = plan_batch(vec![
+            Ok(batch_transaction(8)),
+            Err(AptosError::InvalidAddress),
+            Ok(batch_transaction(7)),
+            Err(AptosError::RecipientNotRegistered),
+        ]);
+
+        assert_eq!(plan.transactions.iter().map(|(index, _)| *index).collect::<Vec<usize>>(), vec![2, 0]);
+
+        let failures: Vec<(usize, i32, i32)> = plan.failures.iter().map(|(index, error)| (*index, error.stage, error.kind)).collect();
+
+        assert_eq!(failures, vec![
+            (1, TransferStage::Building as i32, TransferErrorKind::InvalidAddress as i32),
+            (3, TransferStage::Building as i32, TransferErrorKind::RecipientNotRegistered as i32),
+        ]);
+    }
+
+    #[test]
+    fn entries_after_a_rejected_one_are_blocked() {
+        let transactions = vec![(0, batch_transaction(5)), (1, batch_transaction(6)), (2, batch_transaction(7))];
+        let released: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        let submit = |index: usize, _: String| async move {
+            return match index {
+                1 => Err(transfer_error(TransferStage::Submitting, AptosError::InvalidSequenceNumber)),
+                _ => Ok(format!("0x0{}", index)),
+            };
+        };
+
+        let submission = crate::async_dispatch::RUNTIME.block_on(submit_in_order(transactions, submit, |transaction| {
+            released.lock().unwrap().push(transaction.to_string());
+        }));
+
+        assert_eq!(submission.submitted, vec![(0, "0x00".to_string())]);
+
+        let failures: Vec<(usize, i32)> = submission.failures.iter().map(|(index, error)| (*index, error.kind)).collect();
+
+        assert_eq!(failures, vec![
+            (1, TransferErrorKind::InvalidSequenceNumber as i32),
+            (2, TransferErrorKind::Blocked as i32),
+        ]);
+        assert_eq!(*released.lock().unwrap(), vec![batch_transaction(7)]);
+    }
+}
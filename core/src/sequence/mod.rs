@@ -0,0 +1,453 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::aptos::*;
+use crate::async_dispatch::RUNTIME;
+use crate::core_proto::{event, TransactionExpiredEvent};
+use crate::events::EventEmitter;
+
+const EXPIRATION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sequence number handed out and not known to be committed yet
+#[derive(Debug, Clone)]
+pub struct PendingSequenceNumber {
+    pub sequence_number: u64,
+    /// Set once the transaction is accepted by the node
+    pub hash: Option<String>,
+    /// Transaction as produced by `generate_transaction`, set once accepted by the node
+    pub transaction: Option<String>,
+    /// Unix timestamp in seconds after which the transaction can't be committed anymore
+    pub expiration_secs: u64,
+}
+
+#[derive(Default)]
+struct AccountSequence {
+    /// Sequence number of the account on chain when last synced, or after the last committed transaction
+    chain_sequence_number: u64,
+    /// Next number after the pending ones
+    next_sequence_number: u64,
+    pending: BTreeMap<u64, PendingSequenceNumber>,
+    /// Set when a transaction is not submitted, rejected or expired, the account is synced before the next allocation
+    stale: bool,
+}
+
+impl AccountSequence {
+    /// Numbers between the chain and the pending ones no transaction uses, the next transactions wait for them.
+    /// Numbers allocated to transactions never submitted are gaps once expired.
+    fn gaps(&self, now: u64) -> Vec<u64> {
+        return (self.chain_sequence_number..self.next_sequence_number)
+            .filter(|sequence_number| match self.pending.get(sequence_number) {
+                Some(pending) => pending.hash.is_none() && pending.expiration_secs <= now,
+                None => true,
+            })
+            .collect();
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        return self.pending.values().any(|pending| pending.expiration_secs <= now);
+    }
+
+    fn commit(&mut self, sequence_number: u64) {
+        self.pending.retain(|pending_number, _| *pending_number > sequence_number);
+        self.chain_sequence_number = self.chain_sequence_number.max(sequence_number + 1);
+        self.next_sequence_number = self.next_sequence_number.max(self.chain_sequence_number);
+    }
+
+    /// Returns the submitted transactions that expired
+    fn sync(&mut self, chain_sequence_number: u64, now: u64) -> Vec<PendingSequenceNumber> {
+        // Transactions below the chain number are committed, the others can't be once expired
+        self.pending.retain(|sequence_number, _| *sequence_number >= chain_sequence_number);
+
+        let expired: Vec<PendingSequenceNumber> = self.pending.values()
+            .filter(|pending| pending.expiration_secs <= now)
+            .cloned()
+            .collect();
+
+        self.pending.retain(|_, pending| pending.expiration_secs > now);
+
+        self.chain_sequence_number = chain_sequence_number;
+        self.next_sequence_number = match self.pending.keys().next_back() {
+            Some(last) => last + 1,
+            None => chain_sequence_number,
+        };
+        self.stale = false;
+
+        return expired.into_iter().filter(|pending| pending.hash.is_some()).collect();
+    }
+}
+
+/// Hands out the sequence numbers of the accounts sending from the core, so that transactions sent quickly
+/// from the same account get increasing numbers instead of reading the same one from the node.
+/// The account is synced from chain on the first allocation and after a transaction expires or fails,
+/// the numbers left unused by these transactions are handed out first to unblock the next ones.
+/// Submitted transactions that expire are reported with a `TransactionExpiredEvent`.
+pub struct SequenceNumberManager {
+    accounts: Arc<Mutex<HashMap<AccountAddress, AccountSequence>>>,
+    sync_locks: Arc<SyncLocks>,
+    rest_client: AptosRestClient,
+    event_emitter: Arc<EventEmitter>,
+    task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl SequenceNumberManager {
+    pub fn new(rest_client: AptosRestClient, event_emitter: Arc<EventEmitter>) -> Self {
+        let accounts: Arc<Mutex<HashMap<AccountAddress, AccountSequence>>> = Arc::new(Mutex::new(HashMap::new()));
+        let sync_locks: Arc<SyncLocks> = Arc::new(Mutex::new(HashMap::new()));
+
+        let task = RUNTIME.spawn(expiration_loop(
+            rest_client.clone(),
+            event_emitter.clone(),
+            accounts.clone(),
+            sync_locks.clone(),
+        ));
+
+        return Self {
+            accounts: accounts,
+            sync_locks: sync_locks,
+            rest_client: rest_client,
+            event_emitter: event_emitter,
+            task: Mutex::new(Some(task)),
+        };
+    }
+
+    /// Returns the sequence number of the next transaction of the account, pending until it is committed or expires.
+    /// The number is handed out again when it is released, or when the transaction generated with it expired without being submitted
+    pub async fn allocate(&self, address: &AccountAddress) -> Result<u64, AptosError> {
+        let sync_lock = account_sync_lock(&self.sync_locks, address);
+        let _sync = sync_lock.lock().await;
+
+        let now = chain_now_secs(&self.rest_client);
+
+        let needs_sync = match self.accounts.lock().unwrap().get(address) {
+            Some(account) => account.stale || account.is_expired(now),
+            None => true,
+        };
+
+        if needs_sync {
+            if let Err(error) = sync_account(&self.rest_client, &self.event_emitter, &self.accounts, address).await {
+                return Err(error);
+            }
+        }
+
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(*address).or_insert_with(AccountSequence::default);
+
+        let sequence_number = match account.gaps(now).first() {
+            Some(gap) => *gap,
+            None => {
+                account.next_sequence_number += 1;
+                account.next_sequence_number - 1
+            },
+        };
+
+        account.pending.insert(sequence_number, PendingSequenceNumber {
+            sequence_number: sequence_number,
+            hash: None,
+            transaction: None,
+            // The transaction generated next with the number expires no earlier
+            expiration_secs: now + TRANSACTION_EXPIRATION_SECS,
+        });
+
+        return Ok(sequence_number);
+    }
+
+    /// Records the transaction generated with an allocated number, the number is kept until the transaction expires
+    pub fn generated(&self, address: &AccountAddress, sequence_number: u64, transaction: &str) {
+        let expiration_secs = match transaction_expiration_secs(transaction) {
+            Some(expiration_secs) => expiration_secs,
+            None => return,
+        };
+
+        if let Some(pending) = self.accounts.lock().unwrap().get_mut(address).and_then(|account| account.pending.get_mut(&sequence_number)) {
+            if pending.hash.is_none() {
+                pending.expiration_secs = pending.expiration_secs.max(expiration_secs);
+            }
+        }
+    }
+
+    /// Records the transaction accepted by the node, it replaces the one pending with the same sequence number
+    pub fn submitted(&self, address: &AccountAddress, sequence_number: u64, hash: &str, transaction: &str) {
+        let expiration_secs = transaction_expiration_secs(transaction)
+            .unwrap_or(chain_now_secs(&self.rest_client) + TRANSACTION_EXPIRATION_SECS);
+
+        let mut accounts = self.accounts.lock().unwrap();
+        let account = accounts.entry(*address).or_insert_with(AccountSequence::default);
+
+        account.pending.insert(sequence_number, PendingSequenceNumber {
+            sequence_number: sequence_number,
+            hash: Some(hash.to_string()),
+            transaction: Some(transaction.to_string()),
+            expiration_secs: expiration_secs,
+        });
+
+        account.next_sequence_number = account.next_sequence_number.max(sequence_number + 1);
+    }
+
+    /// The transaction is committed, whether it succeeded or not its sequence number is used
+    pub fn committed(&self, address: &AccountAddress, sequence_number: u64) {
+        if let Some(account) = self.accounts.lock().unwrap().get_mut(address) {
+            account.commit(sequence_number);
+        }
+    }
+
+    /// The transaction was not submitted or was rejected, the account is synced before the next allocation.
+    /// A transaction already accepted with the same sequence number stays pending.
+    pub fn release(&self, address: &AccountAddress, sequence_number: u64) {
+        if let Some(account) = self.accounts.lock().unwrap().get_mut(address) {
+            if account.pending.get(&sequence_number).map_or(false, |pending| pending.hash.is_none()) {
+                account.pending.remove(&sequence_number);
+            }
+
+            account.stale = true;
+        }
+    }
+
+    /// Returns the pending sequence numbers of the account in order
+    pub fn pending(&self, address: &AccountAddress) -> Vec<PendingSequenceNumber> {
+        return match self.accounts.lock().unwrap().get(address) {
+            Some(account) => account.pending.values().cloned().collect(),
+            None => Vec::new(),
+        };
+    }
+
+    /// Returns the sequence numbers blocking the pending transactions of the account
+    pub fn gaps(&self, address: &AccountAddress) -> Vec<u64> {
+        let now = chain_now_secs(&self.rest_client);
+
+        return match self.accounts.lock().unwrap().get(address) {
+            Some(account) => account.gaps(now),
+            None => Vec::new(),
+        };
+    }
+
+    /// Stops checking expirations, called when the core is freed
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for SequenceNumberManager {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Lock of each account held while it is synced from chain and a number is handed out,
+/// an account waiting for the node doesn't block the allocations of the others
+type SyncLocks = Mutex<HashMap<AccountAddress, Arc<futures::lock::Mutex<()>>>>;
+
+fn account_sync_lock(sync_locks: &SyncLocks, address: &AccountAddress) -> Arc<futures::lock::Mutex<()>> {
+    return sync_locks.lock().unwrap()
+        .entry(*address)
+        .or_insert_with(|| Arc::new(futures::lock::Mutex::new(())))
+        .clone();
+}
+
+/// Sender and sequence number of a transaction produced by `generate_transaction`
+pub fn transaction_sequence_number(transaction: &str) -> Option<(AccountAddress, u64)> {
+    let transaction: serde_json::Value = match serde_json::from_str(transaction) {
+        Ok(transaction) => transaction,
+        Err(_) => return None,
+    };
+
+    let sender = match transaction["sender"].as_str().map(AccountAddress::parse) {
+        Some(Ok(sender)) => sender,
+        _ => return None,
+    };
+
+    return transaction["sequence_number"].as_str()
+        .and_then(|sequence_number| sequence_number.parse::<u64>().ok())
+        .map(|sequence_number| (sender, sequence_number));
+}
+
+fn transaction_expiration_secs(transaction: &str) -> Option<u64> {
+    return serde_json::from_str::<serde_json::Value>(transaction).ok()
+        .and_then(|transaction| transaction["expiration_timestamp_secs"].as_str().and_then(|secs| secs.parse::<u64>().ok()));
+}
+
+/// Expirations are compared with the chain time, the device clock may be wrong
+fn chain_now_secs(rest_client: &AptosRestClient) -> u64 {
+    return rest_client.clock().now_secs().unwrap_or_default();
+}
+
+async fn expiration_loop(
+    rest_client: AptosRestClient,
+    event_emitter: Arc<EventEmitter>,
+    accounts: Arc<Mutex<HashMap<AccountAddress, AccountSequence>>>,
+    sync_locks: Arc<SyncLocks>,
+) {
+    loop {
+        tokio::time::sleep(EXPIRATION_CHECK_INTERVAL).await;
+
+        let now = chain_now_secs(&rest_client);
+
+        let addresses: Vec<AccountAddress> = accounts.lock().unwrap()
+            .iter()
+            .filter(|(_, account)| account.is_expired(now))
+            .map(|(address, _)| *address)
+            .collect();
+
+        for address in addresses {
+            let sync_lock = account_sync_lock(&sync_locks, &address);
+            let _sync = sync_lock.lock().await;
+
+            // Retried on the next check
+            if let Err(error) = sync_account(&rest_client, &event_emitter, &accounts, &address).await {
+                log::error!("Sequence number of {} not synced: {:?}", address, error);
+            }
+        }
+    }
+}
+
+/// Syncs the account from chain and reports the expired transactions, the caller holds the sync lock of the account
+async fn sync_account(
+    rest_client: &AptosRestClient,
+    event_emitter: &EventEmitter,
+    accounts: &Mutex<HashMap<AccountAddress, AccountSequence>>,
+    address: &AccountAddress,
+) -> Result<u64, AptosError> {
+    let account = match rest_client.get_account(address).await {
+        Ok(account) => account,
+        Err(error) => return Err(error),
+    };
+
+    let chain_sequence_number = match account.sequence_number.parse::<u64>() {
+        Ok(sequence_number) => sequence_number,
+        Err(error) => {
+            log::error!("{}", error);
+            return Err(AptosError::InvalidSequenceNumber)
+        },
+    };
+
+    let expired = accounts.lock().unwrap()
+        .entry(*address)
+        .or_insert_with(AccountSequence::default)
+        .sync(chain_sequence_number, chain_now_secs(rest_client));
+
+    // The expiration may have been computed with a drifted clock, it is measured again before the next transaction
+    if !expired.is_empty() {
+        rest_client.clock().invalidate();
+    }
+
+    for pending in expired {
+        let hash = pending.hash.unwrap_or_default();
+
+        log::warn!("Transaction {} of {} expired", hash, address);
+
+        event_emitter.emit(event::Kind::TransactionExpired(TransactionExpiredEvent {
+            address: address.to_string(),
+            sequence_number: pending.sequence_number,
+            hash: hash,
+        }));
+    }
+
+    return Ok(chain_sequence_number);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_dispatch::RustCallback;
+    use crate::rust_data::RustData;
+
+    const NOW: u64 = 1660000000;
+
+    extern "C" fn ignore_event(_: *const std::ffi::c_void, _: RustData) {}
+
+    fn pending(sequence_number: u64, hash: Option<&str>, expiration_secs: u64) -> PendingSequenceNumber {
+        return PendingSequenceNumber {
+            sequence_number: sequence_number,
+            hash: hash.map(|hash| hash.to_string()),
+            transaction: None,
+            expiration_secs: expiration_secs,
+        };
+    }
+
+    fn account(pending_numbers: Vec<PendingSequenceNumber>) -> AccountSequence {
+        let mut account = AccountSequence {
+            chain_sequence_number: 5,
+            next_sequence_number: 5,
+            ..Default::default()
+        };
+
+        for pending in pending_numbers {
+            account.next_sequence_number = account.next_sequence_number.max(pending.sequence_number + 1);
+            account.pending.insert(pending.sequence_number, pending);
+        }
+
+        return account;
+    }
+
+    #[test]
+    fn unsubmitted_numbers_are_gaps_once_expired() {
+        let account = account(vec![
+            pending(5, None, NOW + TRANSACTION_EXPIRATION_SECS),
+            pending(6, None, NOW),
+            pending(7, Some("0x07"), NOW),
+            pending(9, Some("0x09"), NOW + TRANSACTION_EXPIRATION_SECS),
+        ]);
+
+        // 7 was submitted, it is reported as expired instead
+        assert_eq!(account.gaps(NOW), vec![6, 8]);
+        assert!(account.is_expired(NOW));
+    }
+
+    #[test]
+    fn sync_drops_expired_numbers_and_reports_submitted_ones() {
+        let mut account = account(vec![
+            pending(5, None, NOW),
+            pending(6, Some("0x06"), NOW),
+            pending(7, Some("0x07"), NOW + TRANSACTION_EXPIRATION_SECS),
+        ]);
+
+        let expired = account.sync(5, NOW);
+
+        assert_eq!(expired.iter().map(|pending| pending.sequence_number).collect::<Vec<u64>>(), vec![6]);
+        assert_eq!(account.gaps(NOW), vec![5, 6]);
+        assert_eq!(account.next_sequence_number, 8);
+    }
+
+    #[test]
+    fn commit_uses_the_lower_numbers() {
+        let mut account = account(vec![pending(5, Some("0x05"), NOW), pending(6, Some("0x06"), NOW)]);
+
+        account.commit(5);
+
+        assert_eq!(account.chain_sequence_number, 6);
+        assert_eq!(account.pending.keys().cloned().collect::<Vec<u64>>(), vec![6]);
+    }
+
+    #[test]
+    fn unsubmitted_number_is_kept_until_its_transaction_expires() {
+        let (clock, _) = FakeClock::new(NOW);
+        let rest_client = AptosRestClient::with_clock("http://127.0.0.1:9".to_string(), clock);
+        rest_client.clock().observe(&(NOW * 1_000_000).to_string()).unwrap();
+
+        let event_emitter = Arc::new(EventEmitter::new(RustCallback {
+            swift_callback_ptr: std::ptr::null(),
+            callback: ignore_event,
+        }));
+
+        let manager = SequenceNumberManager::new(rest_client, event_emitter);
+        let address = AccountAddress::parse("0xa").unwrap();
+        manager.accounts.lock().unwrap().insert(address, account(vec![]));
+
+        let sequence_number = RUNTIME.block_on(manager.allocate(&address)).unwrap();
+        assert_eq!(sequence_number, 5);
+
+        let transaction = serde_json::json!({
+            "sender": address.to_string(),
+            "sequence_number": "5",
+            "expiration_timestamp_secs": (NOW + TRANSACTION_EXPIRATION_SECS + 30).to_string(),
+        }).to_string();
+
+        manager.generated(&address, sequence_number, &transaction);
+
+        let accounts = manager.accounts.lock().unwrap();
+        assert!(accounts[&address].gaps(NOW + TRANSACTION_EXPIRATION_SECS).is_empty());
+        assert_eq!(accounts[&address].gaps(NOW + TRANSACTION_EXPIRATION_SECS + 30), vec![5]);
+    }
+}
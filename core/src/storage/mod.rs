@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::aptos::AptosError;
+
+const STORAGE_FILE: &str = "core_storage.json";
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Migrations applied in order to bring a document to the current schema,
+/// the schema version of a document is the number of migrations applied to it
+const MIGRATIONS: &[fn(&mut serde_json::Map<String, serde_json::Value>)] = &[
+    migrate_accounts,
+    migrate_transactions,
+    migrate_transaction_amounts,
+    migrate_contacts,
+];
+
+/// Key-value document persisted as a JSON file in a directory given by the host.
+/// Values are kept in memory until the storage is opened, they are written on every change after that.
+pub struct Storage {
+    path: RwLock<Option<PathBuf>>,
+    document: RwLock<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl Storage {
+    pub fn new() -> Self {
+        let mut document = serde_json::Map::new();
+        migrate(&mut document);
+
+        return Self {
+            path: RwLock::new(None),
+            document: RwLock::new(document),
+        };
+    }
+
+    /// Loads the document of the directory and migrates it, returns its schema version
+    /// Values set before opening are replaced by the stored ones
+    pub fn open(&self, directory: &Path) -> Result<u32, AptosError> {
+        if let Err(error) = std::fs::create_dir_all(directory) {
+            log::error!("{}", error);
+            return Err(AptosError::StorageFailed);
+        }
+
+        let path = directory.join(STORAGE_FILE);
+
+        let mut document: serde_json::Map<String, serde_json::Value> = match std::fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice(&bytes) {
+                Ok(document) => document,
+                Err(error) => {
+                    log::error!("{}", error);
+                    return Err(AptosError::StorageFailed)
+                },
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => serde_json::Map::new(),
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::StorageFailed)
+            },
+        };
+
+        let schema_version = migrate(&mut document);
+
+        if let Err(error) = write_document(&path, &document) {
+            return Err(error);
+        }
+
+        *self.document.write().unwrap() = document;
+        *self.path.write().unwrap() = Some(path);
+
+        return Ok(schema_version);
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        return self.read(key).unwrap_or(None);
+    }
+
+    /// Same as `get` but fails when the stored value is invalid, so that it is not overwritten
+    pub fn read<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, AptosError> {
+        let document = self.document.read().unwrap();
+
+        return match document.get(key) {
+            Some(value) => match serde_json::from_value(value.clone()) {
+                Ok(value) => Ok(Some(value)),
+                Err(error) => {
+                    log::error!("Invalid {} in storage: {}", key, error);
+                    Err(AptosError::StorageFailed)
+                },
+            },
+            None => Ok(None),
+        };
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), AptosError> {
+        let value = match serde_json::to_value(value) {
+            Ok(value) => value,
+            Err(error) => {
+                log::error!("{}", error);
+                return Err(AptosError::StorageFailed)
+            },
+        };
+
+        let mut document = self.document.write().unwrap();
+        document.insert(key.to_string(), value);
+
+        return match &*self.path.read().unwrap() {
+            Some(path) => write_document(path, &document),
+            None => Ok(()),
+        };
+    }
+}
+
+/// Unix timestamp in seconds of the stored records
+pub fn now_secs() -> u64 {
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+}
+
+/// Writes to a temporary file first so an interrupted write never leaves a truncated document.
+/// The document is written compact, the transaction history makes most of it.
+fn write_document(path: &Path, document: &serde_json::Map<String, serde_json::Value>) -> Result<(), AptosError> {
+    let temporary_path = path.with_extension("json.tmp");
+
+    let result = std::fs::write(&temporary_path, serde_json::to_vec(document).unwrap())
+        .and_then(|()| std::fs::rename(&temporary_path, path));
+
+    return match result {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            log::error!("{}", error);
+            Err(AptosError::StorageFailed)
+        },
+    };
+}
+
+/// Applies the missing migrations, returns the schema version of the document
+fn migrate(document: &mut serde_json::Map<String, serde_json::Value>) -> u32 {
+    let schema_version = document.get(SCHEMA_VERSION_KEY)
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0) as usize;
+
+    if schema_version > MIGRATIONS.len() {
+        log::warn!("Storage schema version {} is newer than {}", schema_version, MIGRATIONS.len());
+        return schema_version as u32;
+    }
+
+    for migration in &MIGRATIONS[schema_version..] {
+        migration(document);
+    }
+
+    document.insert(SCHEMA_VERSION_KEY.to_string(), serde_json::json!(MIGRATIONS.len()));
+
+    return MIGRATIONS.len() as u32;
+}
+
+/// Version 1: accounts and shared wallets of the registry
+fn migrate_accounts(document: &mut serde_json::Map<String, serde_json::Value>) {
+    document.entry("accounts").or_insert(serde_json::json!([]));
+}
+
+/// Version 2: transaction history cache
+fn migrate_transactions(document: &mut serde_json::Map<String, serde_json::Value>) {
+    document.entry("transactions").or_insert(serde_json::json!([]));
+}
+
+/// Version 3: amounts and gas fees of the cached transactions, the cache is cleared to fetch them again
+fn migrate_transaction_amounts(document: &mut serde_json::Map<String, serde_json::Value>) {
+    document.insert("transactions".to_string(), serde_json::json!([]));
+}
+
+/// Version 4: address book
+fn migrate_contacts(document: &mut serde_json::Map<String, serde_json::Value>) {
+    document.entry("contacts").or_insert(serde_json::json!([]));
+}
@@ -6,20 +6,46 @@ use crate::core_proto::*;
 use crate::requests::*;
 use crate::rust_data::RustData;
 
-#[allow(unused_variables)] // TODO: remove this later
 pub fn dispatch_request(core: *const Core, request: Request) -> RustData {
     assert!(!core.is_null());
 
-    let core_arc = unsafe { Arc::from_raw(core) };
+    // The host keeps its own reference until `free_core`
+    let core_arc = unsafe {
+        Arc::increment_strong_count(core);
+        Arc::from_raw(core)
+    };
 
-    use crate::core_proto::request::SyncRequests::{GetSyncBacktrace, CreateAccount, CreateWallet};
+    use crate::core_proto::request::SyncRequests::{GetSyncBacktrace, CreateAccount, CreateWallet, Subscribe, Unsubscribe, WatchAddress, UnwatchAddress, SetWatcherInterval, AddBundleSignature, MergeTransactionBundles, InspectTransactionBundle, SignMessage, VerifyMessage, ValidateAddress, OpenStorage, ListAccounts, GetRegisteredAccount, RegisterAccount, UpdateAccount, DeleteAccount, AddContact, UpdateContact, DeleteContact, FindContacts, ValidateTypeTag, GetPendingTransactions};
 
     let bytes = match request.sync_requests {
         Some(req) => {
             match req {
                 GetSyncBacktrace(get_sync_backtrace_req) => handle_get_backtrace(get_sync_backtrace_req).encode_to_vec(),
-                CreateAccount(create_account_req) => handle_create_account(create_account_req).encode_to_vec(),
-                CreateWallet(create_wallet_req) => handle_create_wallet(create_wallet_req).encode_to_vec(),
+                CreateAccount(create_account_req) => handle_create_account(core_arc, create_account_req).encode_to_vec(),
+                CreateWallet(create_wallet_req) => handle_create_wallet(core_arc, create_wallet_req).encode_to_vec(),
+                Subscribe(subscribe_req) => handle_subscribe(core_arc, subscribe_req).encode_to_vec(),
+                Unsubscribe(unsubscribe_req) => handle_unsubscribe(core_arc, unsubscribe_req).encode_to_vec(),
+                WatchAddress(watch_address_req) => handle_watch_address(core_arc, watch_address_req).encode_to_vec(),
+                UnwatchAddress(unwatch_address_req) => handle_unwatch_address(core_arc, unwatch_address_req).encode_to_vec(),
+                SetWatcherInterval(set_watcher_interval_req) => handle_set_watcher_interval(core_arc, set_watcher_interval_req).encode_to_vec(),
+                AddBundleSignature(add_signature_req) => handle_add_bundle_signature(add_signature_req).encode_to_vec(),
+                MergeTransactionBundles(merge_bundles_req) => handle_merge_transaction_bundles(merge_bundles_req).encode_to_vec(),
+                InspectTransactionBundle(inspect_bundle_req) => handle_inspect_transaction_bundle(inspect_bundle_req).encode_to_vec(),
+                SignMessage(sign_message_req) => handle_sign_message(core_arc, sign_message_req).encode_to_vec(),
+                VerifyMessage(verify_message_req) => handle_verify_message(core_arc, verify_message_req).encode_to_vec(),
+                ValidateAddress(validate_address_req) => handle_validate_address(validate_address_req).encode_to_vec(),
+                OpenStorage(open_storage_req) => handle_open_storage(core_arc, open_storage_req).encode_to_vec(),
+                ListAccounts(list_accounts_req) => handle_list_accounts(core_arc, list_accounts_req).encode_to_vec(),
+                GetRegisteredAccount(get_account_req) => handle_get_registered_account(core_arc, get_account_req).encode_to_vec(),
+                RegisterAccount(register_account_req) => handle_register_account(core_arc, register_account_req).encode_to_vec(),
+                UpdateAccount(update_account_req) => handle_update_account(core_arc, update_account_req).encode_to_vec(),
+                DeleteAccount(delete_account_req) => handle_delete_account(core_arc, delete_account_req).encode_to_vec(),
+                AddContact(add_contact_req) => handle_add_contact(core_arc, add_contact_req).encode_to_vec(),
+                UpdateContact(update_contact_req) => handle_update_contact(core_arc, update_contact_req).encode_to_vec(),
+                DeleteContact(delete_contact_req) => handle_delete_contact(core_arc, delete_contact_req).encode_to_vec(),
+                FindContacts(find_contacts_req) => handle_find_contacts(core_arc, find_contacts_req).encode_to_vec(),
+                ValidateTypeTag(validate_type_tag_req) => handle_validate_type_tag(validate_type_tag_req).encode_to_vec(),
+                GetPendingTransactions(get_pending_transactions_req) => handle_get_pending_transactions(core_arc, get_pending_transactions_req).encode_to_vec(),
             }
         },
         None => panic!("Unhandled synchronous request"),
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::aptos::*;
+use crate::async_dispatch::RUNTIME;
+use crate::core_proto::*;
+use crate::events::EventEmitter;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Last known state of a watched account, `None` until the first successful poll
+#[derive(Default)]
+struct WatchedAccount {
+    balance: Option<u64>,
+    sequence_number: Option<u64>,
+    /// Sequence number of the next sent transaction to report
+    next_transaction: Option<u64>,
+    /// Sequence number of the next received transfer event to report
+    next_received_event: Option<u64>,
+}
+
+/// Polls the watched addresses on the runtime and reports balance changes,
+/// incoming transfers, new transactions and sequence number moves as events.
+/// The ledger info is refreshed on the same loop to report a node serving another chain.
+pub struct AccountWatcher {
+    accounts: Arc<Mutex<HashMap<AccountAddress, WatchedAccount>>>,
+    interval: Arc<Mutex<Duration>>,
+    task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+impl AccountWatcher {
+    pub fn new(rest_client: AptosRestClient, event_emitter: Arc<EventEmitter>) -> Self {
+        let accounts: Arc<Mutex<HashMap<AccountAddress, WatchedAccount>>> = Arc::new(Mutex::new(HashMap::new()));
+        let interval = Arc::new(Mutex::new(DEFAULT_INTERVAL));
+
+        let task = RUNTIME.spawn(poll_loop(
+            rest_client,
+            event_emitter,
+            accounts.clone(),
+            interval.clone(),
+        ));
+
+        return Self {
+            accounts: accounts,
+            interval: interval,
+            task: Mutex::new(Some(task)),
+        };
+    }
+
+    /// Starts watching an address, returns the watched addresses
+    pub fn watch(&self, address: AccountAddress) -> Vec<AccountAddress> {
+        let mut accounts = self.accounts.lock().unwrap();
+
+        accounts.entry(address).or_insert_with(WatchedAccount::default);
+
+        return accounts.keys().cloned().collect();
+    }
+
+    /// Stops watching an address, returns the watched addresses
+    pub fn unwatch(&self, address: &AccountAddress) -> Vec<AccountAddress> {
+        let mut accounts = self.accounts.lock().unwrap();
+
+        accounts.remove(address);
+
+        return accounts.keys().cloned().collect();
+    }
+
+    /// Changes the polling interval, returns the interval applied
+    pub fn set_interval(&self, interval: Duration) -> Duration {
+        let interval = std::cmp::max(interval, MIN_INTERVAL);
+
+        *self.interval.lock().unwrap() = interval;
+
+        return interval;
+    }
+
+    /// Stops polling, called when the core is freed
+    pub fn stop(&self) {
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for AccountWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn poll_loop(
+    rest_client: AptosRestClient,
+    event_emitter: Arc<EventEmitter>,
+    accounts: Arc<Mutex<HashMap<AccountAddress, WatchedAccount>>>,
+    interval: Arc<Mutex<Duration>>,
+) {
+    loop {
+        let duration = *interval.lock().unwrap();
+        tokio::time::sleep(duration).await;
+
+        // The ledger info is fetched again once old, a failure is retried on the next tick
+        if rest_client.ensure_clock_synced().await.is_ok() {
+            if let Some(chain_id) = rest_client.take_chain_change() {
+                event_emitter.emit(event::Kind::NodeSwitched(NodeSwitchedEvent {
+                    url: rest_client.url().to_string(),
+                    chain_id: chain_id as u32,
+                }));
+            }
+        }
+
+        let addresses: Vec<AccountAddress> = accounts.lock().unwrap().keys().cloned().collect();
+
+        for address in addresses {
+            poll_account(&rest_client, &event_emitter, &accounts, &address).await;
+        }
+    }
+}
+
+async fn poll_account(
+    rest_client: &AptosRestClient,
+    event_emitter: &EventEmitter,
+    accounts: &Mutex<HashMap<AccountAddress, WatchedAccount>>,
+    address: &AccountAddress,
+) {
+    let (next_transaction, next_received_event) = match accounts.lock().unwrap().get(address) {
+        Some(account) => (account.next_transaction, account.next_received_event),
+        None => return,
+    };
+
+    // Requests are made without holding the lock, a failed request keeps the previous state
+    let balance = rest_client.get_account_balance(address).await.ok();
+
+    let sequence_number = match rest_client.get_account(address).await {
+        Ok(account) => account.sequence_number.parse::<u64>().ok(),
+        Err(_) => None,
+    };
+
+    // The first poll only records where the history ends, the older transactions are not reported
+    let transactions = match next_transaction {
+        Some(start) => transactions_since(rest_client, address, start).await.ok(),
+        None => None,
+    };
+
+    let received_events = match next_received_event {
+        Some(start) => received_events_since(rest_client, address, start).await.ok(),
+        None => None,
+    };
+
+    let received_event_count = match next_received_event {
+        Some(_) => None,
+        None => rest_client.get_received_event_count(address).await.ok(),
+    };
+
+    let mut events: Vec<event::Kind> = Vec::new();
+
+    {
+        let mut accounts = accounts.lock().unwrap();
+
+        // The address may have been unwatched in the meantime
+        let account = match accounts.get_mut(address) {
+            Some(account) => account,
+            None => return,
+        };
+
+        if let Some(new_balance) = balance {
+            if let Some(old_balance) = account.balance {
+                if new_balance != old_balance {
+                    events.push(event::Kind::BalanceChanged(BalanceChangedEvent {
+                        address: address.to_string(),
+                        old_balance: old_balance,
+                        new_balance: new_balance,
+                    }));
+                }
+            }
+
+            account.balance = Some(new_balance);
+        }
+
+        if let Some(new_sequence_number) = sequence_number {
+            if let Some(old_sequence_number) = account.sequence_number {
+                if new_sequence_number != old_sequence_number {
+                    events.push(event::Kind::SequenceNumberChanged(SequenceNumberChangedEvent {
+                        address: address.to_string(),
+                        old_sequence_number: old_sequence_number,
+                        new_sequence_number: new_sequence_number,
+                    }));
+                }
+            }
+
+            account.sequence_number = Some(new_sequence_number);
+
+            if account.next_transaction.is_none() {
+                account.next_transaction = Some(new_sequence_number);
+            }
+        }
+
+        if let (Some(start), Some(transactions)) = (next_transaction, transactions) {
+            account.next_transaction = Some(start + transactions.len() as u64);
+
+            for transaction in transactions {
+                events.push(event::Kind::NewTransaction(NewTransactionEvent {
+                    address: address.to_string(),
+                    transaction: Some(crate::core_proto::Transaction {
+                        type_transaction: transaction.type_transaction,
+                        hash: transaction.hash,
+                    }),
+                }));
+            }
+        }
+
+        if let Some(count) = received_event_count {
+            account.next_received_event = Some(count);
+        }
+
+        if let (Some(start), Some(received_events)) = (next_received_event, received_events) {
+            account.next_received_event = Some(start + received_events.len() as u64);
+
+            for received_event in received_events {
+                match received_event.data["amount"].as_str().and_then(|s| s.parse::<u64>().ok()) {
+                    Some(amount) => events.push(event::Kind::IncomingTransfer(IncomingTransferEvent {
+                        address: address.to_string(),
+                        amount: amount,
+                    })),
+                    None => log::warn!("Received event {} without amount", received_event.sequence_number),
+                };
+            }
+        }
+    }
+
+    for event in events {
+        event_emitter.emit(event);
+    }
+}
+
+/// Reads every page of the transactions sent by the account from a sequence number
+async fn transactions_since(
+    rest_client: &AptosRestClient,
+    address: &AccountAddress,
+    start: u64,
+) -> Result<Vec<model::Transaction>, AptosError> {
+    let mut transactions: Vec<model::Transaction> = Vec::new();
+
+    loop {
+        let page = match rest_client.get_account_transactions_page(address, Some(start + transactions.len() as u64), Some(TRANSACTIONS_PAGE_LIMIT)).await {
+            Ok(page) => page,
+            Err(error) => return Err(error),
+        };
+
+        let is_last_page = page.len() < TRANSACTIONS_PAGE_LIMIT as usize;
+
+        transactions.extend(page);
+
+        if is_last_page {
+            return Ok(transactions);
+        }
+    }
+}
+
+/// Reads every page of the transfers received by the account from an event sequence number
+async fn received_events_since(
+    rest_client: &AptosRestClient,
+    address: &AccountAddress,
+    start: u64,
+) -> Result<Vec<model::AccountEvent>, AptosError> {
+    let mut received_events: Vec<model::AccountEvent> = Vec::new();
+
+    loop {
+        let page = match rest_client.get_received_events(address, start + received_events.len() as u64, TRANSACTIONS_PAGE_LIMIT).await {
+            Ok(page) => page,
+            Err(error) => return Err(error),
+        };
+
+        let is_last_page = page.len() < TRANSACTIONS_PAGE_LIMIT as usize;
+
+        received_events.extend(page);
+
+        if is_last_page {
+            return Ok(received_events);
+        }
+    }
+}